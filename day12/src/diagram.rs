@@ -0,0 +1,158 @@
+use crate::hashlife::{first_cell_of_compressed, hash_next_n_compressed, window_of_compressed, Automaton,
+                      Index, State, EMPTY};
+use num_bigint::{BigInt, BigUint};
+use num_traits::Zero;
+use std::fs::File;
+use std::io::Write;
+
+//-----------------------------------------------------------------------------
+// TYPE
+
+/// the part of the line that is displayed
+pub enum Window
+{
+   /// always displays the cells from `start` to `start+width`
+   Fixed
+   {
+      start: BigInt, width: usize
+   },
+   /// displays `width` cells starting `margin` cells before the first non empty cell of each generation
+   Follow
+   {
+      margin: usize, width: usize
+   }
+}
+
+/// a generation as displayed in the window
+#[derive(Clone)]
+pub struct Row
+{
+   pub generation: BigUint,
+   pub start: BigInt,
+   pub cells: Vec<Index>
+}
+
+//-----------------------------------------------------------------------------
+// SAMPLING
+
+/// returns the generations from start (included) to end (excluded) by step
+pub fn generation_range(start: &BigUint, end: &BigUint, step: &BigUint) -> Vec<BigUint>
+{
+   let mut result = Vec::new();
+   let mut generation = start.clone();
+   while &generation < end
+   {
+      result.push(generation.clone());
+      generation += step;
+   }
+   result
+}
+
+/// extracts the cells of a state, compressed at the given level, that are visible in the window
+fn window_row(state: &State,
+              level: usize,
+              automaton: &Automaton,
+              generation: &BigUint,
+              window: &Window)
+              -> Row
+{
+   let (start, width) = match window
+   {
+      Window::Fixed { start, width } => (start.clone(), *width),
+      Window::Follow { margin, width } =>
+      {
+         let first_cell = first_cell_of_compressed(state, level, automaton).unwrap_or_else(BigInt::zero);
+         (first_cell - BigInt::from(*margin), *width)
+      }
+   };
+
+   let cells = window_of_compressed(state, level, automaton, &start, width);
+   Row { generation: generation.clone(), start, cells }
+}
+
+/// calls `on_row` on each of the given generations
+/// each generation is reached from the initial state with the HashLife
+/// and only the blocks that overlap the window are decompressed
+/// (the memoized rules are shared between samples so later jumps reuse the work done for earlier ones)
+pub fn for_each_sample<F>(state: &State,
+                          automaton: &mut Automaton,
+                          generations: &[BigUint],
+                          window: &Window,
+                          mut on_row: F)
+   where F: FnMut(&Row)
+{
+   for generation in generations
+   {
+      let (compressed, level) = hash_next_n_compressed(state, automaton, generation);
+      on_row(&window_row(&compressed, level, automaton, generation, window));
+   }
+}
+
+/// collects the rows of the given generations
+pub fn sample_rows(state: &State,
+                   automaton: &mut Automaton,
+                   generations: &[BigUint],
+                   window: &Window)
+                   -> Vec<Row>
+{
+   let mut rows = Vec::new();
+   for_each_sample(state, automaton, generations, window, |row| rows.push(row.clone()));
+   rows
+}
+
+//-----------------------------------------------------------------------------
+// OUTPUT
+
+/// writes a space-time diagram as a binary PGM image, one row of pixels per generation
+/// empty cells are white and the other states go darker as their index grows
+pub fn write_pgm(path: &str, rows: &[Row], states: usize, scale: usize)
+{
+   let width = rows.iter().map(|row| row.cells.len()).max().unwrap_or(0);
+   let mut file = File::create(path).expect("Failed to create image file.");
+   write!(file, "P5\n{} {}\n255\n", width * scale, rows.len() * scale).expect("Failed to write header.");
+
+   let max_state = std::cmp::max(1, states - 1);
+   for row in rows
+   {
+      let mut line = Vec::new();
+      for i in 0..width
+      {
+         let cell = row.cells.get(i).cloned().unwrap_or(EMPTY);
+         let gray = 255 - (cell * 255 / max_state) as u8;
+         line.extend(std::iter::repeat_n(gray, scale));
+      }
+      for _ in 0..scale
+      {
+         file.write_all(&line).expect("Failed to write image.");
+      }
+   }
+}
+
+/// turns a row into a line of coloured ANSI blocks prefixed by its generation and window start
+pub fn ansi_row(row: &Row) -> String
+{
+   let mut line = format!("{:>12} {:>8} ", row.generation, row.start);
+   for &cell in &row.cells
+   {
+      match cell
+      {
+         EMPTY => line += "\x1b[40m ",
+         _ => line += &format!("\x1b[4{}m ", 1 + (cell - 1) % 7)
+      }
+   }
+   line += "\x1b[0m";
+   line
+}
+
+/// streams the given generations to the terminal, waiting `delay_ms` between two generations
+pub fn stream_ansi(state: &State,
+                   automaton: &mut Automaton,
+                   generations: &[BigUint],
+                   window: &Window,
+                   delay_ms: u64)
+{
+   for_each_sample(state, automaton, generations, window, |row| {
+      println!("{}", ansi_row(row));
+      std::thread::sleep(std::time::Duration::from_millis(delay_ms));
+   });
+}
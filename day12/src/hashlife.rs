@@ -0,0 +1,627 @@
+use num_bigint::{BigInt, BigUint};
+use num_traits::{ToPrimitive, Zero};
+use std::collections::HashMap;
+use std::fmt;
+
+//-----------------------------------------------------------------------------
+// TYPE
+
+/// a cell state at the base level or a block of cells at higher levels
+/// the index 0 is always the empty cell (and the empty block at every level)
+pub type Index = usize;
+pub const EMPTY: Index = 0;
+
+/// a line of cells (or blocks), the shift being the position of the first one
+pub struct State
+{
+   pub shift: BigInt,
+   pub vec: Vec<Index>
+}
+
+/// a 1D cellular automaton, each cell looks at `radius` neigbours on each side and can take `states` values
+/// blocks of `block` cells are compressed into a single index at each level of the HashLife
+/// if `max_entries` is set, the caches are kept around that number of entries :
+/// - during a step, memoized rules are dropped when they reach half the ceiling (they can be recomputed)
+/// - between steps, if the ceiling is reached, patterns that cannot be reached from the state are collected
+pub struct Automaton
+{
+   pub radius: usize,
+   pub states: usize,
+   pub block: usize,
+   pub max_entries: Option<usize>,
+   table: Vec<Index>,                            // base rule, indexed by the neighbourhood in base `states`
+   rules: HashMap<Vec<Index>, Index>,            // memoized result of a window of blocks
+   index_of_pattern: HashMap<Vec<Index>, Index>, // compress patterns into indexes
+   pattern_of_index: Vec<Vec<Index>>,            // decompress indexes into patterns
+   level_of_index: Vec<usize>,                   // level at which each index lives
+   weights: HashMap<Index, (BigInt, BigInt)>,    // number of non empty cells in a block and sum of offsets
+   hits: u64,
+   misses: u64,
+   evictions: u64,
+   collections: u64
+}
+
+/// a snapshot of the cache usage of an automaton
+pub struct CacheStats
+{
+   pub hits: u64,
+   pub misses: u64,
+   pub evictions: u64,
+   pub collections: u64,
+   pub patterns_per_level: Vec<usize>,
+   pub rules_per_level: Vec<usize>
+}
+
+/// why a rule cannot be turned into an automaton
+#[derive(Debug, PartialEq)]
+pub enum RuleError
+{
+   BlockTooSmall(usize),
+   PatternMismatch(Vec<Index>, Index),
+   NumberTooLarge(u128),
+   EmptyNeighbourhoodNotEmpty(Index)
+}
+
+impl fmt::Display for RuleError
+{
+   fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+   {
+      match self
+      {
+         RuleError::BlockTooSmall(block) => write!(f, "the block factor should be at least 2 (got {})", block),
+         RuleError::PatternMismatch(pattern, result) =>
+         {
+            write!(f, "the rule {:?} => {} does not match the radius and number of states", pattern, result)
+         }
+         RuleError::NumberTooLarge(number) =>
+         {
+            write!(f, "the rule number {} is too large for the radius and number of states", number)
+         }
+         RuleError::EmptyNeighbourhoodNotEmpty(result) =>
+         {
+            write!(f, "an empty neighbourhood should produce an empty cell (got {})", result)
+         }
+      }
+   }
+}
+
+//-----------------------------------------------------------------------------
+// CONSTRUCTION
+
+impl Automaton
+{
+   /// builds an automaton from a list of neighbourhoods and their results
+   /// missing neighbourhoods produce an empty cell
+   pub fn new(radius: usize,
+              states: usize,
+              block: usize,
+              rules: &HashMap<Vec<Index>, Index>)
+              -> Result<Automaton, RuleError>
+   {
+      let width = 2 * radius + 1;
+      let mut table = vec![EMPTY; states.pow(width as u32)];
+      for (pattern, &result) in rules
+      {
+         if pattern.len() != width || result >= states || pattern.iter().any(|&cell| cell >= states)
+         {
+            return Err(RuleError::PatternMismatch(pattern.clone(), result));
+         }
+         table[pattern.iter().fold(0, |acc, &cell| acc * states + cell)] = result;
+      }
+
+      Automaton::from_table(radius, states, block, table)
+   }
+
+   /// builds an automaton from a Wolfram rule number
+   /// (the n-th digit, in base `states`, of the rule number is the result of the n-th neighbourhood)
+   /// the empty neighbourhood must produce an empty cell (as the empty block is shared by all levels)
+   /// which rejects the odd elementary rules
+   pub fn from_wolfram(number: u128, radius: usize, states: usize, block: usize) -> Result<Automaton, RuleError>
+   {
+      let width = 2 * radius + 1;
+      let mut digits = number;
+      let mut table = Vec::new();
+      for _ in 0..states.pow(width as u32)
+      {
+         table.push((digits % states as u128) as Index);
+         digits /= states as u128;
+      }
+      if digits != 0
+      {
+         return Err(RuleError::NumberTooLarge(number));
+      }
+
+      Automaton::from_table(radius, states, block, table)
+   }
+
+   /// builds an automaton from a complete base rule table
+   fn from_table(radius: usize, states: usize, block: usize, table: Vec<Index>) -> Result<Automaton, RuleError>
+   {
+      if block < 2
+      {
+         return Err(RuleError::BlockTooSmall(block));
+      }
+      // the empty block is shared by all levels, it needs to stay empty
+      if table[0] != EMPTY
+      {
+         return Err(RuleError::EmptyNeighbourhoodNotEmpty(table[0]));
+      }
+
+      // the base states are reserved indexes that are never decompressed
+      let empty_block = vec![EMPTY; block];
+      let pattern_of_index = vec![empty_block.clone(); states];
+      let mut index_of_pattern = HashMap::new();
+      index_of_pattern.insert(empty_block, EMPTY);
+
+      Ok(Automaton { radius,
+                     states,
+                     block,
+                     max_entries: None,
+                     table,
+                     rules: HashMap::new(),
+                     index_of_pattern,
+                     pattern_of_index,
+                     level_of_index: vec![0; states],
+                     weights: HashMap::new(),
+                     hits: 0,
+                     misses: 0,
+                     evictions: 0,
+                     collections: 0 })
+   }
+}
+
+//-----------------------------------------------------------------------------
+// NAIVE
+
+/// adds the given number of empty cells on both sides of a state
+pub fn expand_state(state: &State, expansion: usize) -> State
+{
+   // new vec
+   let mut vec = vec![EMPTY; expansion];
+   let suffix = vec![EMPTY; expansion];
+   vec.extend(&state.vec);
+   vec.extend(suffix);
+   // new shift
+   let shift = &state.shift - BigInt::from(expansion);
+   State { shift, vec }
+}
+
+/// removes empty cells on both side of a state
+/// (an empty state always ends up with a shift of 0)
+pub fn contracts_state(state: &State) -> State
+{
+   match state.vec.iter().position(|&index| index != EMPTY)
+   {
+      None => State { shift: BigInt::zero(), vec: Vec::new() },
+      Some(start) =>
+      {
+         let end = state.vec.iter().rposition(|&index| index != EMPTY).unwrap() + 1;
+         State { shift: &state.shift + BigInt::from(start), vec: state.vec[start..end].to_vec() }
+      }
+   }
+}
+
+/// gets the result of a window of 2*radius+1 indexes of the given level
+fn apply_rules(pattern: &[Index], level: usize, automaton: &mut Automaton) -> Index
+{
+   if level == 0
+   {
+      return automaton.table[pattern.iter().fold(0, |acc, &cell| acc * automaton.states + cell)];
+   }
+
+   match automaton.rules.get(pattern)
+   {
+      Some(&index) =>
+      {
+         automaton.hits += 1;
+         index
+      }
+      None =>
+      {
+         automaton.misses += 1;
+         let mut state = State { shift: BigInt::zero(), vec: pattern.to_vec() };
+
+         // each step at this level is `block` steps at the level below
+         state = decompress_state(&state, automaton);
+         for _ in 0..automaton.block
+         {
+            state = next_state(&state, level - 1, automaton);
+         }
+
+         // 2*radius+1 blocks reduce to a single block after `block` steps
+         let index = compress_pattern(&state.vec, level, automaton);
+         evict_rules_if_needed(automaton);
+         automaton.rules.insert(pattern.to_vec(), index);
+         index
+      }
+   }
+}
+
+/// goes to the next state of the given level
+/// WARNING: requires some buffering as it will truncate the state
+fn next_state(state: &State, level: usize, automaton: &mut Automaton) -> State
+{
+   let radius = automaton.radius;
+   let vec = &state.vec;
+   let mut new_vec = Vec::new();
+
+   for i in radius..(vec.len() - radius)
+   {
+      let index = apply_rules(&vec[(i - radius)..=(i + radius)], level, automaton);
+      new_vec.push(index);
+   }
+
+   State { shift: &state.shift + BigInt::from(radius), vec: new_vec }
+}
+
+/// goes forward by n steps of the given level
+fn next_n_state_at_level(state: &State, level: usize, automaton: &mut Automaton, n: usize) -> State
+{
+   let mut state = expand_state(state, n * 2 * automaton.radius);
+
+   for _ in 0..n
+   {
+      state = next_state(&state, level, automaton);
+      // between two steps, the state is the only thing that refers to indexes
+      state = collect_garbage_if_needed(state, automaton);
+   }
+
+   contracts_state(&state)
+}
+
+/// goes forward by n steps, one step at a time
+pub fn next_n_state(state: &State, automaton: &mut Automaton, n: usize) -> State
+{
+   next_n_state_at_level(state, 0, automaton, n)
+}
+
+//-----------------------------------------------------------------------------
+// HASHLIFE
+
+/// takes a pattern of indexes from the level below and produces an index of the given level
+fn compress_pattern(pattern: &[Index], level: usize, automaton: &mut Automaton) -> Index
+{
+   match automaton.index_of_pattern.get(pattern)
+   {
+      Some(index) => *index,
+      None =>
+      {
+         let index = automaton.pattern_of_index.len();
+         automaton.pattern_of_index.push(pattern.to_vec());
+         automaton.level_of_index.push(level);
+         automaton.index_of_pattern.insert(pattern.to_vec(), index);
+         index
+      }
+   }
+}
+
+/// express each block of indexes of the given level as a single index of the level above
+fn compress_state(state: &State, level: usize, automaton: &mut Automaton) -> State
+{
+   let block = automaton.block;
+
+   // insures that the shift starts with a multiple of the block factor
+   let block_size = BigInt::from(block);
+   let buffer_size = (((&state.shift % &block_size) + &block_size) % &block_size).to_usize().unwrap();
+   let mut vec = vec![EMPTY; buffer_size];
+   vec.extend(&state.vec);
+   // and that the vec can be cut in full blocks
+   let padding = (block - vec.len() % block) % block;
+   vec.extend(vec![EMPTY; padding]);
+
+   let vec = vec.chunks(block).map(|pattern| compress_pattern(pattern, level + 1, automaton)).collect();
+   let shift = (&state.shift - BigInt::from(buffer_size)) / block_size;
+   State { shift, vec }
+}
+
+/// express each index as a block of indexes
+fn decompress_state(state: &State, automaton: &Automaton) -> State
+{
+   let vec: Vec<Index> =
+      state.vec.iter().flat_map(|&index| automaton.pattern_of_index[index].iter()).cloned().collect();
+   let shift = &state.shift * BigInt::from(automaton.block);
+   State { shift, vec }
+}
+
+/// goes forward by n steps of the given level
+/// returns the state compressed at the highest level reached and that level
+fn hash_next_n_state_at_level(state: &State,
+                              level: usize,
+                              automaton: &mut Automaton,
+                              n: &BigUint)
+                              -> (State, usize)
+{
+   let block = BigUint::from(automaton.block);
+   let steps = (n % &block).to_usize().unwrap();
+   let state = next_n_state_at_level(state, level, automaton, steps);
+
+   let n = n / block;
+   if n.is_zero()
+   {
+      (state, level)
+   }
+   else
+   {
+      let state = compress_state(&state, level, automaton);
+      hash_next_n_state_at_level(&state, level + 1, automaton, &n)
+   }
+}
+
+/// goes forward by n steps
+/// returns the state compressed at the given level (call `decompress_to_cells` to get the actual cells)
+/// WARNING: the indexes of a compressed state are only valid until the next call that steps the automaton
+/// (the garbage collector might renumber them)
+pub fn hash_next_n_compressed(state: &State, automaton: &mut Automaton, n: &BigUint) -> (State, usize)
+{
+   hash_next_n_state_at_level(state, 0, automaton, n)
+}
+
+/// decompresses a state from the given level down to individual cells
+/// WARNING: the result can be huge if the pattern grew with time
+#[cfg(test)]
+pub fn decompress_to_cells(state: &State, level: usize, automaton: &Automaton) -> State
+{
+   let mut state = contracts_state(state);
+   for _ in 0..level
+   {
+      state = contracts_state(&decompress_state(&state, automaton));
+   }
+   state
+}
+
+/// goes forward by n steps
+#[cfg(test)]
+pub fn hash_next_n_state(state: &State, automaton: &mut Automaton, n: &BigUint) -> State
+{
+   let (state, level) = hash_next_n_compressed(state, automaton, n);
+   decompress_to_cells(&state, level, automaton)
+}
+
+/// returns the position of the first non empty cell of a state compressed at the given level
+/// only the blocks on the path to that cell are decompressed
+pub fn first_cell_of_compressed(state: &State, level: usize, automaton: &Automaton) -> Option<BigInt>
+{
+   let block = BigInt::from(automaton.block);
+   let (i, &index) = state.vec.iter().enumerate().find(|&(_, &index)| index != EMPTY)?;
+   let mut size = num_traits::pow(block.clone(), level);
+   let mut position = (&state.shift + BigInt::from(i)) * &size;
+
+   // a non empty block always has a non empty sub-block
+   let mut index = index;
+   for _ in 0..level
+   {
+      size /= &block;
+      let (rank, &sub_index) =
+         automaton.pattern_of_index[index].iter().enumerate().find(|&(_, &index)| index != EMPTY).unwrap();
+      position += BigInt::from(rank) * &size;
+      index = sub_index;
+   }
+
+   Some(position)
+}
+
+/// returns the `width` cells starting at `start` of a state compressed at the given level
+/// only the blocks that overlap the window are decompressed
+pub fn window_of_compressed(state: &State,
+                            level: usize,
+                            automaton: &Automaton,
+                            start: &BigInt,
+                            width: usize)
+                            -> Vec<Index>
+{
+   /// copies the cells of the block, whose first cell is at `position`, that fall into the window
+   fn copy_block(index: Index,
+                 level: usize,
+                 position: BigInt,
+                 automaton: &Automaton,
+                 start: &BigInt,
+                 cells: &mut Vec<Index>)
+   {
+      let size = num_traits::pow(BigInt::from(automaton.block), level);
+      let end = start + BigInt::from(cells.len());
+      if index == EMPTY || &position + &size <= *start || position >= end
+      {
+         return;
+      }
+      if level == 0
+      {
+         cells[(position - start).to_usize().unwrap()] = index;
+         return;
+      }
+
+      let sub_size = size / BigInt::from(automaton.block);
+      for (rank, &sub_index) in automaton.pattern_of_index[index].iter().enumerate()
+      {
+         let sub_position = &position + BigInt::from(rank) * &sub_size;
+         copy_block(sub_index, level - 1, sub_position, automaton, start, cells);
+      }
+   }
+
+   let size = num_traits::pow(BigInt::from(automaton.block), level);
+   let mut cells = vec![EMPTY; width];
+   for (i, &index) in state.vec.iter().enumerate()
+   {
+      let position = (&state.shift + BigInt::from(i)) * &size;
+      copy_block(index, level, position, automaton, start, &mut cells);
+   }
+   cells
+}
+
+//-----------------------------------------------------------------------------
+// EVALUATION
+
+/// returns the number of non empty cells in the block and the sum of their offsets from its start
+fn block_weight(index: Index, level: usize, automaton: &mut Automaton) -> (BigInt, BigInt)
+{
+   if index == EMPTY
+   {
+      return (BigInt::zero(), BigInt::zero());
+   }
+   if level == 0
+   {
+      return (BigInt::from(1), BigInt::zero());
+   }
+   if let Some(weight) = automaton.weights.get(&index)
+   {
+      return weight.clone();
+   }
+
+   // each sub-block is offset by its rank times its size in cells
+   let sub_size = num_traits::pow(BigInt::from(automaton.block), level - 1);
+   let mut count = BigInt::zero();
+   let mut sum = BigInt::zero();
+   for (rank, sub_index) in automaton.pattern_of_index[index].clone().into_iter().enumerate()
+   {
+      let (sub_count, sub_sum) = block_weight(sub_index, level - 1, automaton);
+      sum += sub_sum + &sub_count * BigInt::from(rank) * &sub_size;
+      count += sub_count;
+   }
+
+   automaton.weights.insert(index, (count.clone(), sum.clone()));
+   (count, sum)
+}
+
+/// returns the sum of the positions of all non empty cells of a state compressed at the given level
+/// without decompressing it
+pub fn evaluate_compressed(state: &State, level: usize, automaton: &mut Automaton) -> BigInt
+{
+   let size = num_traits::pow(BigInt::from(automaton.block), level);
+   let mut total = BigInt::zero();
+
+   for (i, &index) in state.vec.iter().enumerate()
+   {
+      let (count, sum) = block_weight(index, level, automaton);
+      let position = (&state.shift + BigInt::from(i)) * &size;
+      total += sum + count * position;
+   }
+
+   total
+}
+
+//-----------------------------------------------------------------------------
+// CACHE
+
+impl Automaton
+{
+   /// returns the number of entries stored in the caches
+   pub fn cache_entries(&self) -> usize
+   {
+      self.rules.len() + self.pattern_of_index.len() + self.weights.len()
+   }
+
+   /// returns statistics on the cache usage since the creation of the automaton
+   pub fn cache_stats(&self) -> CacheStats
+   {
+      let nb_levels = self.level_of_index.iter().max().map_or(1, |level| level + 1);
+      let mut patterns_per_level = vec![0; nb_levels];
+      for &level in &self.level_of_index[self.states..]
+      {
+         patterns_per_level[level] += 1;
+      }
+
+      // a window of indexes of a given level produces an index of the level above
+      // (the empty window is shared by all levels and counted at the first one)
+      let mut rules_per_level = vec![0; nb_levels + 1];
+      for pattern in self.rules.keys()
+      {
+         let level = pattern.iter().map(|&index| self.level_of_index[index]).max().unwrap_or(0);
+         rules_per_level[level + 1] += 1;
+      }
+
+      CacheStats { hits: self.hits,
+                   misses: self.misses,
+                   evictions: self.evictions,
+                   collections: self.collections,
+                   patterns_per_level,
+                   rules_per_level }
+   }
+}
+
+/// drops all memoized rules if they grew beyond half the ceiling
+/// this is safe at any time as rules are only a cache over the base table
+fn evict_rules_if_needed(automaton: &mut Automaton)
+{
+   match automaton.max_entries
+   {
+      Some(max_entries) if automaton.rules.len() >= max_entries / 2 =>
+      {
+         automaton.rules.clear();
+         automaton.evictions += 1;
+      }
+      _ => ()
+   }
+}
+
+/// collects the garbage if the caches grew beyond their ceiling
+fn collect_garbage_if_needed(state: State, automaton: &mut Automaton) -> State
+{
+   match automaton.max_entries
+   {
+      Some(max_entries) if automaton.cache_entries() > max_entries => collect_garbage(state, automaton),
+      _ => state
+   }
+}
+
+/// keeps only the patterns reachable from the live state and the rules that connect them
+/// indexes are renumbered compactly (base states keep their indexes) and the live state is rewritten
+fn collect_garbage(state: State, automaton: &mut Automaton) -> State
+{
+   /// copies an index, and all the indexes it refers to, into the new tables
+   fn renumber(index: Index,
+               automaton: &Automaton,
+               new_index: &mut HashMap<Index, Index>,
+               pattern_of_index: &mut Vec<Vec<Index>>,
+               level_of_index: &mut Vec<usize>)
+               -> Index
+   {
+      if let Some(&index) = new_index.get(&index)
+      {
+         return index;
+      }
+
+      let pattern = automaton.pattern_of_index[index]
+         .iter()
+         .map(|&sub_index| renumber(sub_index, automaton, new_index, pattern_of_index, level_of_index))
+         .collect();
+      let renumbered = pattern_of_index.len();
+      pattern_of_index.push(pattern);
+      level_of_index.push(automaton.level_of_index[index]);
+      new_index.insert(index, renumbered);
+      renumbered
+   }
+
+   let states = automaton.states;
+   let mut new_index: HashMap<Index, Index> = (0..states).map(|index| (index, index)).collect();
+   let mut pattern_of_index = automaton.pattern_of_index[..states].to_vec();
+   let mut level_of_index = automaton.level_of_index[..states].to_vec();
+
+   let mut renumber_root =
+      |index: &Index| renumber(*index, automaton, &mut new_index, &mut pattern_of_index, &mut level_of_index);
+   let vec = state.vec.iter().map(&mut renumber_root).collect();
+
+   // keeps the rules whose window and result are still alive
+   let rules = automaton.rules
+                        .iter()
+                        .filter_map(|(pattern, result)| {
+                           let pattern: Option<Vec<Index>> =
+                              pattern.iter().map(|index| new_index.get(index).cloned()).collect();
+                           Some((pattern?, *new_index.get(result)?))
+                        })
+                        .collect();
+
+   let mut index_of_pattern = HashMap::new();
+   for (index, pattern) in pattern_of_index.iter().enumerate().skip(states)
+   {
+      index_of_pattern.insert(pattern.clone(), index);
+   }
+   index_of_pattern.insert(vec![EMPTY; automaton.block], EMPTY);
+
+   automaton.rules = rules;
+   automaton.index_of_pattern = index_of_pattern;
+   automaton.pattern_of_index = pattern_of_index;
+   automaton.level_of_index = level_of_index;
+   automaton.weights.clear();
+   automaton.collections += 1;
+
+   State { shift: state.shift, vec }
+}
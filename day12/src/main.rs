@@ -1,44 +1,30 @@
-#![feature(slice_patterns)]
 #[macro_use]
 extern crate scan_fmt;
+use num_bigint::{BigInt, BigUint};
+use num_traits::{ToPrimitive, Zero};
 use std::collections::HashMap;
+use std::fmt;
 use std::fs::File;
 use std::io::{BufRead, BufReader};
 
-//-----------------------------------------------------------------------------
-// TYPE
-
-type Index = usize;
-const EMPTY_POT: Index = 0;
-const FULL_POT: Index = 1;
-
-type Pattern = [Index; 5];
-const EMPTY_PATTERN: Pattern = [EMPTY_POT; 5];
-
-struct State
-{
-   shift: i128,
-   vec: Vec<Index>
-}
-
-struct Rules
-{
-   rules: HashMap<Pattern, Index>,
-   index_of_pattern: HashMap<Pattern, Index>, // compress patterns into indexes
-   pattern_of_index: Vec<Pattern>             // decompress indexes into patterns
-}
+mod diagram;
+mod hashlife;
+use diagram::{generation_range, sample_rows, stream_ansi, write_pgm, Window};
+use hashlife::{evaluate_compressed, hash_next_n_compressed, next_n_state, Automaton, CacheStats, Index, State,
+               EMPTY};
 
 //-----------------------------------------------------------------------------
 // INPUT
 
-/// takes a char and returns a plant
+/// takes a char and returns a cell state
+/// '.' and '#' are the two states of the puzzle, digits can be used for automata with more states
 fn plant_of_char(c: char) -> Index
 {
    match c
    {
-      '#' => FULL_POT,
-      '.' => EMPTY_POT,
-      _ => panic!("unable to match the plant")
+      '#' => 1,
+      '.' => 0,
+      _ => c.to_digit(10).expect("unable to match the plant") as Index
    }
 }
 
@@ -46,24 +32,25 @@ fn plant_of_char(c: char) -> Index
 fn parse_init(line: &str) -> State
 {
    let plants = scan_fmt!(&line, "initial state: {}", String).unwrap().chars().map(plant_of_char).collect();
-   State { shift: 0, vec: plants }
+   State { shift: BigInt::zero(), vec: plants }
 }
 
 /// parses a pattern and its assocated result
-fn parse_rule(line: &str) -> (Pattern, Index)
+fn parse_rule(line: &str) -> (Vec<Index>, Index)
 {
    let (rule, plant) = scan_fmt!(&line, "{} => {}", String, char);
    let plant = plant_of_char(plant.unwrap());
    let rule: Vec<Index> = rule.unwrap().chars().map(plant_of_char).collect();
-   match rule[..]
+   if rule.len().is_multiple_of(2)
    {
-      [l1, l2, c, r1, r2] => ([l1, l2, c, r1, r2], plant),
-      _ => panic!("rule of unpredicted size")
+      panic!("rule of unpredicted size");
    }
+   (rule, plant)
 }
 
-/// parses a file and returns a state and rules
-fn input_data(path: &str) -> (State, Rules)
+/// parses a file and returns a state and an automaton
+/// the radius is deduced from the size of the rules and the number of states from the largest state seen
+fn input_data(path: &str) -> (State, Automaton)
 {
    let file = File::open(path).expect("Failed to open input file.");
    let mut lines =
@@ -71,240 +58,434 @@ fn input_data(path: &str) -> (State, Rules)
 
    let initial_state = parse_init(&lines.next().unwrap());
    lines.next();
-   let raw_rules: HashMap<Pattern, Index> = lines.map(|line| parse_rule(&line)).collect();
-
-   let pattern_of_index = vec![EMPTY_PATTERN, EMPTY_PATTERN];
-   let mut index_of_pattern = HashMap::new();
-   index_of_pattern.insert(EMPTY_PATTERN, EMPTY_POT);
-   let rules =
-      Rules { rules: raw_rules, index_of_pattern: index_of_pattern, pattern_of_index: pattern_of_index };
-
-   (initial_state, rules)
+   let raw_rules: HashMap<Vec<Index>, Index> = lines.map(|line| parse_rule(&line)).collect();
+
+   let radius = raw_rules.keys().next().map_or(0, |pattern| pattern.len() / 2);
+   let states = raw_rules.iter()
+                         .flat_map(|(pattern, &result)| pattern.iter().cloned().chain(Some(result)))
+                         .chain(initial_state.vec.iter().cloned())
+                         .max()
+                         .map_or(2, |state| std::cmp::max(2, state + 1));
+   let automaton =
+      Automaton::new(radius, states, 2 * radius + 1, &raw_rules).unwrap_or_else(|error| panic!("{}", error));
+
+   (initial_state, automaton)
 }
 
 //-----------------------------------------------------------------------------
-// TASK1
+// PERIOD
 
-/// adds the given number of empty pots on both sides of a state
-fn expand_state(state: &State, expansion: u128) -> State
+/// the state reproduces itself, up to a translation, every `period` generations once `transient` is reached
+/// `residues[r]` holds the (sum, number of non empty pots) at generation transient+r
+struct Period
 {
-   // new vec
-   let mut vec = vec![EMPTY_POT; expansion as usize];
-   let suffix = vec![EMPTY_POT; expansion as usize];
-   vec.extend(&state.vec);
-   vec.extend(suffix);
-   // new shift
-   let shift = state.shift - (expansion as i128);
-   State { shift, vec }
+   transient: usize,
+   period: usize,
+   shift_per_period: BigInt,
+   residues: Vec<(BigInt, BigInt)>
 }
 
-/// removes empty pots on both side of a state
-fn contracts_state(state: &State) -> State
+/// steps the automaton naively until the contracted state repeats up to translation
+/// returns None if that did not happen within the given number of generations
+fn detect_period(state: &State, automaton: &mut Automaton, max_generations: usize) -> Option<Period>
 {
-   let mut shift = state.shift;
-   let mut vec = &state.vec[..];
+   let mut seen: HashMap<Vec<Index>, (usize, BigInt)> = HashMap::new();
+   let mut history = Vec::new();
+   let mut state = next_n_state(state, automaton, 0);
 
-   loop
+   for generation in 0..=max_generations
    {
-      match vec
+      if let Some((transient, shift)) = seen.get(&state.vec)
       {
-         [EMPTY_POT, tail..] =>
-         {
-            vec = tail;
-            shift += 1;
-         }
-         [tail.., EMPTY_POT] => vec = tail,
-         _ => return State { shift: shift, vec: vec.to_vec() }
+         let residues = history[*transient..].to_vec();
+         return Some(Period { transient: *transient,
+                              period: generation - transient,
+                              shift_per_period: &state.shift - shift,
+                              residues });
       }
+
+      let count = BigInt::from(state.vec.iter().filter(|&&index| index != EMPTY).count());
+      history.push((evaluate_state(&state), count));
+      seen.insert(state.vec.clone(), (generation, state.shift.clone()));
+      state = next_n_state(&state, automaton, 1);
    }
+
+   None
 }
 
-//-----------------------------------------------
+impl Period
+{
+   /// returns the sum of the pots after n generations, n being at least the transient
+   fn sum_at(&self, n: &BigUint) -> BigInt
+   {
+      let elapsed = n - BigUint::from(self.transient);
+      let period = BigUint::from(self.period);
+      let residue = (&elapsed % &period).to_usize().unwrap();
+      let nb_periods = BigInt::from(elapsed / period);
+
+      let (sum, count) = &self.residues[residue];
+      sum + nb_periods * &self.shift_per_period * count
+   }
+}
 
-/// gets the result of the pattern according to the rules
-fn apply_rules(pattern: &Pattern, mut rules: &mut Rules) -> Index
+impl fmt::Display for Period
 {
-   match rules.rules.get(pattern)
+   /// displays the closed form of the sum as a function of the generation n
+   fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
    {
-      Some(&index) => index,
-      None =>
+      write!(f,
+             "period of {} generation(s) shifting by {} after a transient of {} generation(s)",
+             self.period, self.shift_per_period, self.transient)?;
+      for (residue, (sum, count)) in self.residues.iter().enumerate()
       {
-         let mut state = State { shift: 0, vec: pattern.to_vec() };
-
-         // each step in compressed form is 5 steps in decompressed form
-         state = decompress_state(&state, &rules);
-         for _ in 0..5
+         let slope = &self.shift_per_period * count;
+         if self.period == 1
          {
-            state = next_state(&state, &mut rules);
+            let start = self.transient;
+            write!(f, "\n   sum(n) = {} + {} * (n - {}) for n >= {}", sum, slope, start, start)?;
+         }
+         else
+         {
+            let start = self.transient + residue;
+            write!(f, "\n   sum({} + {}k) = {} + {} * k", start, self.period, sum, slope)?;
          }
-         state = compress_state(&state, &mut rules);
-
-         // 5 patterns reduce to a single pattern after 5 steps
-         let index = state.vec[0];
-         rules.rules.insert(*pattern, index);
-         index
       }
+      Ok(())
    }
 }
 
-/// goes to the next state
-/// WARNING: requires some buffering as it will truncate the state
-fn next_state(state: &State, mut rules: &mut Rules) -> State
+//-----------------------------------------------------------------------------
+// CHECK
+
+/// displays the cache statistics of an automaton
+fn display_stats(name: &str, stats: &CacheStats)
 {
-   let vec = &state.vec;
-   let mut new_vec = Vec::new();
+   println!("cache for {} : {} hits, {} misses, {} evictions, {} garbage collections",
+            name, stats.hits, stats.misses, stats.evictions, stats.collections);
+   println!("   patterns per level : {:?}", stats.patterns_per_level);
+   println!("   rules per level : {:?}", stats.rules_per_level);
+}
+//-----------------------------------------------------------------------------
+// MAIN
 
-   for i in 2..(vec.len() - 2)
+/// returns the argument that follows the given flag on the command line
+fn flag_value(flag: &str) -> Option<String>
+{
+   let args: Vec<String> = std::env::args().collect();
+   args.windows(2).find(|pair| pair[0] == flag).map(|pair| pair[1].clone())
+}
+
+/// returns the value of a state (sum of, potentially negativ, indexes of non empty pots)
+/// NOTE: the state is expected to be fully decompressed
+fn evaluate_state(state: &State) -> BigInt
+{
+   let mut sum = BigInt::zero();
+
+   for (i, &index) in state.vec.iter().enumerate()
    {
-      let pattern = [vec[i - 2], vec[i - 1], vec[i], vec[i + 1], vec[i + 2]];
-      let index = apply_rules(&pattern, &mut rules);
-      new_vec.push(index);
+      match index
+      {
+         EMPTY => (),
+         _ => sum += BigInt::from(i) + &state.shift
+      }
    }
 
-   State { shift: state.shift + 2, vec: new_vec }
+   sum
 }
 
-/// goes forward by n steps
-fn next_n_state(state: &State, mut rules: &mut Rules, n: u128) -> State
+fn main()
 {
-   let mut state = expand_state(&state, n * 4);
+   // `--input PATH` runs on another input, such as `./data/challenge.txt` which never settles
+   let input_path = flag_value("--input").unwrap_or_else(|| String::from("./data/input.txt"));
+   let (initial_state, mut automaton) = input_data(&input_path);
+   // the caches are bounded so that they cannot exhaust the memory
+   automaton.max_entries = Some(1_000_000);
+
+   // short steps
+   let short_time = 20;
+   let state_after_short = next_n_state(&initial_state, &mut automaton, short_time);
+   let score_after_short = evaluate_state(&state_after_short);
+   println!("score after short time : {}", score_after_short);
 
-   for _ in 0..n
+   // period detection, the closed form it gives is only a cross-check of the HashLife
+   let long_time = BigUint::from(50_000_000_000u64);
+   let max_generations = 1000;
+   let period = detect_period(&initial_state, &mut automaton, max_generations);
+   match &period
    {
-      state = next_state(&state, &mut rules);
+      None => println!("no period found within {} generations of {}", max_generations, input_path),
+      Some(period) => println!("{}", period)
    }
 
-   contracts_state(&state)
+   // space-time diagrams are optional, `--diagram PATH` draws the first generations as an image
+   // and `--stream` fast-forwards through the generations in the terminal
+   if let Some(image_path) = flag_value("--diagram")
+   {
+      let first_generations =
+         generation_range(&BigUint::from(0u32), &BigUint::from(200u32), &BigUint::from(1u32));
+      let window = Window::Fixed { start: BigInt::from(-20), width: 300 };
+      let rows = sample_rows(&initial_state, &mut automaton, &first_generations, &window);
+      write_pgm(&image_path, &rows, automaton.states, 2);
+      println!("space-time diagram written to {}", image_path);
+   }
+   if std::env::args().any(|arg| arg == "--stream")
+   {
+      let sampled_generations: Vec<BigUint> =
+         (0..12).map(|k| num_traits::pow(BigUint::from(10u32), k)).collect();
+      let window = Window::Follow { margin: 2, width: 100 };
+      stream_ansi(&initial_state, &mut automaton, &sampled_generations, &window, 0);
+   }
+
+   // `--wolfram RULE` runs an elementary rule from a single cell with a bounded cache
+   if let Some(rule) = flag_value("--wolfram")
+   {
+      let rule = rule.parse().expect("The rule should be a number.");
+      match Automaton::from_wolfram(rule, 1, 2, 3)
+      {
+         Err(error) => println!("rule {} rejected : {}", rule, error),
+         Ok(mut elementary) =>
+         {
+            elementary.max_entries = Some(100_000);
+            let single_cell = State { shift: BigInt::zero(), vec: vec![1] };
+            let (state, level) = hash_next_n_compressed(&single_cell, &mut elementary, &BigUint::from(10_000u32));
+            let score = evaluate_compressed(&state, level, &mut elementary);
+            println!("score of rule {} after 10000 steps : {}", rule, score);
+            display_stats(&format!("rule {}", rule), &elementary.cache_stats());
+         }
+      }
+   }
+
+   // long steps, the state is evaluated without being decompressed
+   // this does not depend on the period, an input that never settles only makes it slower
+   let (state_after_long, level) = hash_next_n_compressed(&initial_state, &mut automaton, &long_time);
+   let score_after_long = evaluate_compressed(&state_after_long, level, &mut automaton);
+   println!("score after long time : {}", score_after_long);
+   if let Some(period) = &period
+   {
+      let closed_form = period.sum_at(&long_time);
+      let verdict = if closed_form == score_after_long { "agrees" } else { "disagrees" };
+      println!("the closed form {} : {}", verdict, closed_form);
+   }
+   display_stats("the input rules", &automaton.cache_stats());
 }
 
 //-----------------------------------------------------------------------------
-// TASK2
+// TESTS
 
-/// takes a pattern and produces an index
-fn compress_pattern(pattern: &Pattern, rules: &mut Rules) -> Index
+#[cfg(test)]
+mod tests
 {
-   match rules.index_of_pattern.get(pattern)
+   use super::*;
+   use hashlife::hash_next_n_state;
+
+   /// compares the HashLife with naive stepping on a few random initial states
+   fn check_against_naive(name: &str, mut automaton: Automaton, seed: u64)
    {
-      Some(index) => *index,
-      None =>
+      let mut random = seed;
+      for &size in [1, 8, 30].iter()
       {
-         let index = rules.pattern_of_index.len();
-         rules.pattern_of_index.push(*pattern);
-         rules.index_of_pattern.insert(*pattern, index);
-         index
+         let vec = (0..size).map(|_| {
+                               random = random.wrapping_mul(6_364_136_223_846_793_005).wrapping_add(1);
+                               ((random >> 33) % automaton.states as u64) as Index
+                            })
+                            .collect();
+         let state = State { shift: BigInt::zero(), vec };
+
+         for &n in [0, 1, 2, 7, 24, 25, 26, 131, 300].iter()
+         {
+            let naive = next_n_state(&state, &mut automaton, n);
+            let hashed = hash_next_n_state(&state, &mut automaton, &BigUint::from(n));
+            if naive.shift != hashed.shift || naive.vec != hashed.vec
+            {
+               panic!("HashLife diverges from naive stepping for {} after {} steps!", name, n);
+            }
+
+            let (compressed, level) = hash_next_n_compressed(&state, &mut automaton, &BigUint::from(n));
+            if evaluate_compressed(&compressed, level, &mut automaton) != evaluate_state(&naive)
+            {
+               panic!("Compressed evaluation diverges from naive stepping for {} after {} steps!", name, n);
+            }
+         }
       }
    }
-}
 
-/// express each block of 5 indexes as a single index
-fn compress_state(state: &State, mut rules: &mut Rules) -> State
-{
-   // insures that the shift starts with a multiple of 5
-   if state.shift % 5 != 0
+   #[test]
+   fn input_rules_match_naive()
    {
-      let buffer_size = (state.shift % 5) as u128;
-      let state = expand_state(&state, buffer_size);
-      return compress_state(&state, &mut rules);
+      let (_, automaton) = input_data("./data/input.txt");
+      check_against_naive("the input rules", automaton, 12);
    }
 
-   // get the indexes by block of 5 (patterns) and stores the corresponding indexes
-   let mut vec = Vec::new();
-   let mut old_vec = &state.vec[..];
-   while !old_vec.is_empty()
+   #[test]
+   fn elementary_rules_match_naive()
    {
-      match old_vec
+      for &rule in [30, 90, 110, 184].iter()
       {
-         [i0, i1, i2, i3, i4, tail..] =>
-         {
-            let pattern = [*i0, *i1, *i2, *i3, *i4];
-            let index = compress_pattern(&pattern, &mut rules);
-            vec.push(index);
-            old_vec = tail;
-         }
-         _ =>
-         {
-            let mut it = old_vec.iter();
-            let i0 = it.next().unwrap_or(&EMPTY_POT);
-            let i1 = it.next().unwrap_or(&EMPTY_POT);
-            let i2 = it.next().unwrap_or(&EMPTY_POT);
-            let i3 = it.next().unwrap_or(&EMPTY_POT);
-            let i4 = it.next().unwrap_or(&EMPTY_POT);
-            let pattern = [*i0, *i1, *i2, *i3, *i4];
-            let index = compress_pattern(&pattern, &mut rules);
-            vec.push(index);
-            old_vec = &[];
-         }
+         let automaton = Automaton::from_wolfram(rule, 1, 2, 3).expect("Valid rule.");
+         check_against_naive(&format!("elementary rule {}", rule), automaton, rule as u64);
       }
    }
 
-   let shift = state.shift / 5;
-   State { shift, vec }
-}
+   #[test]
+   fn odd_elementary_rules_are_rejected()
+   {
+      for rule in (1..256).step_by(2)
+      {
+         let result = Automaton::from_wolfram(rule, 1, 2, 3).map(|_| ());
+         assert_eq!(result, Err(hashlife::RuleError::EmptyNeighbourhoodNotEmpty(1)), "rule {}", rule);
+      }
+      let result = Automaton::from_wolfram(256, 1, 2, 3).map(|_| ());
+      assert_eq!(result, Err(hashlife::RuleError::NumberTooLarge(256)));
+   }
 
-/// express each index as a block of 5 index
-fn decompress_state(state: &State, rules: &Rules) -> State
-{
-   let vec: Vec<Index> =
-      state.vec.iter().flat_map(|&index| rules.pattern_of_index[index].iter()).cloned().collect();
-   let shift = state.shift * 5;
-   State { shift, vec }
-}
+   #[test]
+   fn three_states_rule_matches_naive()
+   {
+      let automaton = Automaton::from_wolfram(2_248_943_238_213, 1, 3, 4).expect("Valid rule.");
+      check_against_naive("a 3 states rule", automaton, 3);
+   }
 
-//-----------------------------------------------
+   #[test]
+   fn radius_three_rule_matches_naive()
+   {
+      let automaton = Automaton::from_wolfram(0xDEAD_BEEF_0BAD_CAFE_1234_5678, 3, 2, 7).expect("Valid rule.");
+      check_against_naive("a radius 3 rule", automaton, 7);
+   }
 
-/// goes forward by n steps
-fn hash_next_n_state(state: &State, mut rules: &mut Rules, n: u128) -> State
-{
-   let mut state = next_n_state(&state, &mut rules, n % 5);
+   #[test]
+   fn chaotic_rule_stays_under_the_cache_ceiling()
+   {
+      // rule 30 never settles, without a ceiling its caches would grow with every generation
+      let max_entries = 5_000;
+      let mut bounded = Automaton::from_wolfram(30, 1, 2, 3).expect("Valid rule.");
+      bounded.max_entries = Some(max_entries);
+      let mut unbounded = Automaton::from_wolfram(30, 1, 2, 3).expect("Valid rule.");
+      let single_cell = State { shift: BigInt::zero(), vec: vec![1] };
+      for &n in [500u32, 1000, 2000].iter()
+      {
+         let n = BigUint::from(n);
+         let sum = compressed_sum(&single_cell, &mut bounded, &n);
+         assert!(bounded.cache_entries() <= max_entries, "{} entries after {} steps", bounded.cache_entries(), n);
+         assert_eq!(sum, compressed_sum(&single_cell, &mut unbounded, &n), "after {} steps", n);
+      }
+      let stats = bounded.cache_stats();
+      assert!(stats.evictions > 0 && stats.collections > 0, "the ceiling was never reached");
+      assert!(unbounded.cache_entries() > max_entries, "the ceiling is too high to be tested");
+   }
 
-   if n / 5 == 0
+   #[test]
+   fn tiny_cache_matches_naive()
    {
-      state
+      let mut automaton = Automaton::from_wolfram(110, 1, 2, 3).expect("Valid rule.");
+      automaton.max_entries = Some(50);
+      check_against_naive("a tiny cache", automaton, 110);
    }
-   else
+
+   /// sum of the pots after n generations, evaluated without decompressing the state
+   fn compressed_sum(state: &State, automaton: &mut Automaton, n: &BigUint) -> BigInt
    {
-      state = compress_state(&state, &mut rules);
-      state = hash_next_n_state(&state, &mut rules, n / 5);
-      state = decompress_state(&state, &rules);
-      contracts_state(&state)
+      let (state, level) = hash_next_n_compressed(state, automaton, n);
+      evaluate_compressed(&state, level, automaton)
    }
-}
 
-//-----------------------------------------------------------------------------
-// MAIN
+   #[test]
+   fn compressed_sum_matches_decompressed()
+   {
+      let (initial_state, mut automaton) = input_data("./data/input.txt");
+      let long_time = BigUint::from(50_000_000_000u64);
+      let state = hash_next_n_state(&initial_state, &mut automaton, &long_time);
+      assert_eq!(compressed_sum(&initial_state, &mut automaton, &long_time), evaluate_state(&state));
+   }
 
-/// returns the value of a state (sum of, potentially negativ, indexes of non empty pots)
-fn evaluate_state(state: &State) -> i128
-{
-   let mut sum = 0;
+   #[test]
+   fn sum_after_googol_generations()
+   {
+      // the input settles into a shape that drifts at constant speed, its sum then grows linearly
+      let (initial_state, mut automaton) = input_data("./data/input.txt");
+      let settled_time = BigUint::from(1000u32);
+      let huge_time = num_traits::pow(BigUint::from(10u32), 100);
+      let settled_sum = compressed_sum(&initial_state, &mut automaton, &settled_time);
+      let slope = compressed_sum(&initial_state, &mut automaton, &(&settled_time + 1u32)) - &settled_sum;
+      let huge_sum = compressed_sum(&initial_state, &mut automaton, &huge_time);
+      assert_eq!(huge_sum, settled_sum + slope * BigInt::from(huge_time - settled_time));
+   }
 
-   for (i, &index) in state.vec.iter().enumerate()
+   /// checks the closed form given by the period against the HashLife
+   fn check_period(initial_state: &State, automaton: &mut Automaton, generations: &[BigUint])
    {
-      match index
+      let period = detect_period(initial_state, automaton, 10_000).expect("No period found!");
+      for n in generations.iter().filter(|&n| n >= &BigUint::from(period.transient))
       {
-         FULL_POT => sum += (i as i128) + state.shift,
-         EMPTY_POT => (),
-         _ => panic!("A compressed index survived into the evaluation phase!")
+         assert_eq!(period.sum_at(n), compressed_sum(initial_state, automaton, n), "after {} generations", n);
       }
    }
 
-   sum
-}
+   #[test]
+   fn input_period_matches_hashlife()
+   {
+      let (initial_state, mut automaton) = input_data("./data/input.txt");
+      let generations = [BigUint::from(1000u32),
+                         BigUint::from(50_000_000_000u64),
+                         num_traits::pow(BigUint::from(10u32), 100)];
+      check_period(&initial_state, &mut automaton, &generations);
+   }
 
-fn main()
-{
-   let input_path = "./data/challenge.txt";
-   let (initial_state, mut rules) = input_data(input_path);
+   #[test]
+   fn glider_period_matches_hashlife()
+   {
+      // rule 184 moves a lone cell one pot to the right at each generation
+      let mut automaton = Automaton::from_wolfram(184, 1, 2, 3).expect("Valid rule.");
+      let single_cell = State { shift: BigInt::zero(), vec: vec![1] };
+      let period = detect_period(&single_cell, &mut automaton, 10).expect("No period found!");
+      assert_eq!((period.transient, period.period, period.shift_per_period), (0, 1, BigInt::from(1)));
+      let generations: Vec<BigUint> = (0..30).map(|k| num_traits::pow(BigUint::from(3u32), k)).collect();
+      check_period(&single_cell, &mut automaton, &generations);
+   }
 
-   // short steps
-   let short_time = 20;
-   let state_after_short = next_n_state(&initial_state, &mut rules, short_time);
-   let score_after_short = evaluate_state(&state_after_short);
-   println!("score after short time : {}", score_after_short);
+   #[test]
+   fn growing_pattern_has_no_period()
+   {
+      let mut automaton = Automaton::from_wolfram(90, 1, 2, 3).expect("Valid rule.");
+      let single_cell = State { shift: BigInt::zero(), vec: vec![1] };
+      assert!(detect_period(&single_cell, &mut automaton, 500).is_none());
+   }
 
-   // long steps
-   let long_time = 50_000_000_000; // 1_000_000_000_000_000_000_000_000_000_000_000_000
-   let state_after_long = hash_next_n_state(&initial_state, &mut rules, long_time);
-   let score_after_long = evaluate_state(&state_after_long);
-   println!("score after long time : {}", score_after_long);
+   /// compares the rows sampled from the compressed states with windows cut out of the decompressed states
+   fn check_windows(initial_state: &State, automaton: &mut Automaton, generations: &[BigUint])
+   {
+      let windows =
+         [Window::Fixed { start: BigInt::from(-37), width: 90 }, Window::Follow { margin: 3, width: 70 }];
+      for window in windows.iter()
+      {
+         let rows = sample_rows(initial_state, automaton, generations, window);
+         for row in rows
+         {
+            let state = hash_next_n_state(initial_state, automaton, &row.generation);
+            if let Window::Follow { margin, .. } = window
+            {
+               let start = &state.shift - BigInt::from(*margin);
+               assert_eq!(row.start, start, "wrong start after {} generations", row.generation);
+            }
+            let cell = |i: usize| {
+               let i = (&row.start + BigInt::from(i) - &state.shift).to_usize();
+               i.and_then(|i| state.vec.get(i).cloned()).unwrap_or(EMPTY)
+            };
+            let cells: Vec<Index> = (0..row.cells.len()).map(cell).collect();
+            assert_eq!(row.cells, cells, "after {} generations", row.generation);
+         }
+      }
+   }
+
+   #[test]
+   fn windows_match_decompressed()
+   {
+      let generations: Vec<BigUint> =
+         [0u32, 1, 7, 24, 25, 26, 131, 300, 1000].iter().map(|&n| BigUint::from(n)).collect();
+      let (initial_state, mut automaton) = input_data("./data/input.txt");
+      check_windows(&initial_state, &mut automaton, &generations);
+      let mut chaotic = Automaton::from_wolfram(30, 1, 2, 3).expect("Valid rule.");
+      chaotic.max_entries = Some(1000);
+      let single_cell = State { shift: BigInt::zero(), vec: vec![1] };
+      check_windows(&single_cell, &mut chaotic, &generations);
+   }
 }
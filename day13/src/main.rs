@@ -1,4 +1,6 @@
 #![feature(slice_patterns)]
+use std::collections::HashMap;
+use std::fmt;
 use std::fs::File;
 use std::io::{BufRead, BufReader};
 
@@ -36,28 +38,119 @@ enum Turn
    Right
 }
 
+/// a cart, identified by its rank in reading order on the initial map
 #[derive(Clone, Copy, PartialEq)]
 struct Cart
 {
+   id: usize,
+   row: usize,
+   col: usize,
    direction: Direction,
    turn: Turn,
-   last_tick: usize
+   crashed: bool
 }
 
-type Terrain = [Vec<Road>];
-type Vehicules = [Vec<Option<Cart>>];
+/// describe how a cart picks its next turn after going through an intersection
+#[derive(Clone, PartialEq)]
+enum Policy
+{
+   /// Left -> Straight -> Right -> Left... (the puzzle's rule)
+   RoundRobin,
+   /// a turn drawn at random with a xorshift generator
+   Random
+   {
+      state: u64
+   },
+   /// the given sequence of turns, repeated forever
+   Sequence
+   {
+      turns: Vec<Turn>, next: usize
+   }
+}
 
-//-----------------------------------------------------------------------------
-// INPUT
+/// all the carts, kept in reading order, and an index from position to rank in the list
+/// the intersection policy of each cart is stored at the index given by its id
+#[derive(Clone)]
+struct Traffic
+{
+   carts: Vec<Cart>,
+   positions: HashMap<(usize, usize), usize>,
+   policies: Vec<Policy>
+}
 
-/// makes a new cart with the default direction
-fn new_cart(dir: Direction) -> Cart
+/// a collision between a moving cart and the cart it ran into
+#[derive(Clone, Copy, PartialEq)]
+struct Collision
 {
-   Cart { direction: dir, turn: Turn::Left, last_tick: 0 }
+   row: usize,
+   col: usize,
+   moving_cart: usize,
+   hit_cart: usize
 }
 
-/// identifies the road and cart displayed by a char
-fn parse_char(c: char) -> (Road, Option<Cart>)
+/// all the collisions that happened during a tick, in the order in which they happened
+struct TickEvents
+{
+   tick: usize,
+   collisions: Vec<Collision>
+}
+
+/// the last cart standing and the tick at the end of which it was left alone
+/// (no tick if the cart was alone from the start)
+struct Survivor
+{
+   id: usize,
+   row: usize,
+   col: usize,
+   tick: Option<usize>
+}
+
+/// full history of a run : every tick that saw at least one collision and the last cart standing
+/// (if the number of carts is even or the run was stopped early, no cart survives)
+struct Timeline
+{
+   ticks: Vec<TickEvents>,
+   survivor: Option<Survivor>
+}
+
+/// a problem, found on the track before simulation, that would make a cart leave the road
+/// (or a cart that left the road during the simulation of a track that was not validated)
+#[derive(Debug, PartialEq)]
+enum TrackError
+{
+   UnknownGlyph
+   {
+      row: usize, col: usize, glyph: char
+   },
+   DisconnectedCorner
+   {
+      row: usize, col: usize
+   },
+   DisconnectedStraight
+   {
+      row: usize, col: usize
+   },
+   IncompleteIntersection
+   {
+      row: usize, col: usize
+   },
+   MisplacedCart
+   {
+      row: usize, col: usize
+   },
+   CartOffTrack
+   {
+      row: usize, col: usize
+   }
+}
+
+type Terrain = [Vec<Road>];
+
+//-----------------------------------------------------------------------------
+// INPUT
+
+/// identifies the road and the direction of the cart displayed by a char
+fn parse_char(c: char) -> (Road, Option<Direction>)
 {
    match c
    {
@@ -69,62 +162,433 @@ fn parse_char(c: char) -> (Road, Option<Cart>)
       '/' => (Road::LeftCorner, None),
       '\\' => (Road::RightCorner, None),
       // cart
-      '>' => (Road::Horizontal, Some(new_cart(Direction::Right))),
-      '<' => (Road::Horizontal, Some(new_cart(Direction::Left))),
-      '^' => (Road::Vertical, Some(new_cart(Direction::Up))),
-      'v' => (Road::Vertical, Some(new_cart(Direction::Down))),
+      '>' => (Road::Horizontal, Some(Direction::Right)),
+      '<' => (Road::Horizontal, Some(Direction::Left)),
+      '^' => (Road::Vertical, Some(Direction::Up)),
+      'v' => (Road::Vertical, Some(Direction::Down)),
       //'X' => (Road::NoRoad, Cart::Collision),
       // error
       _ => panic!("Unrecognisezd char!")
    }
 }
 
-/// parses a line into a road and carts
-fn parse_line(line: &str) -> (Vec<Road>, Vec<Option<Cart>>)
+/// parses a line into a road and cart directions
+fn parse_line(line: &str) -> (Vec<Road>, Vec<Option<Direction>>)
 {
    line.chars().map(parse_char).unzip()
 }
 
-/// parses a file and returns (points, speeds)
-fn input_data(path: &str) -> (Vec<Vec<Road>>, Vec<Vec<Option<Cart>>>)
+/// reads the lines of a file
+fn input_data(path: &str) -> Vec<String>
 {
    let file = File::open(path).expect("Failed to open input file.");
-   BufReader::new(file).lines()
-                       .map(|line_option| line_option.expect("Failed to load a line."))
-                       .map(|line| parse_line(&line))
-                       .unzip()
+   BufReader::new(file).lines().map(|line_option| line_option.expect("Failed to load a line.")).collect()
+}
+
+/// parses a (validated) track and returns (terrain, carts)
+/// carts are given ids in reading order
+fn parse_track(lines: &[String]) -> (Vec<Vec<Road>>, Traffic)
+{
+   let (terrain, directions): (Vec<Vec<Road>>, Vec<Vec<Option<Direction>>>) =
+      lines.iter().map(|line| parse_line(line)).unzip();
+
+   let mut carts = Vec::new();
+   for (row, line) in directions.iter().enumerate()
+   {
+      for (col, direction) in line.iter().enumerate()
+      {
+         if let Some(direction) = *direction
+         {
+            carts.push(Cart { id: carts.len(), row, col, direction, turn: Turn::Left, crashed: false });
+         }
+      }
+   }
+
+   let policies = vec![Policy::RoundRobin; carts.len()];
+   let mut traffic = Traffic { carts, positions: HashMap::new(), policies };
+   sort_carts(&mut traffic);
+   (terrain, traffic)
 }
 
 //-----------------------------------------------------------------------------
-// TASKS
+// POLICY
+
+impl Policy
+{
+   /// returns the first turn that a cart following this policy will take
+   fn first_turn(&mut self) -> Turn
+   {
+      match self
+      {
+         Policy::RoundRobin => Turn::Left,
+         _ => self.next_turn(Turn::Left)
+      }
+   }
+
+   /// returns the turn that follows the given one
+   fn next_turn(&mut self, turn: Turn) -> Turn
+   {
+      match self
+      {
+         Policy::RoundRobin => match turn
+         {
+            Turn::Left => Turn::Straight,
+            Turn::Straight => Turn::Right,
+            Turn::Right => Turn::Left
+         },
+         Policy::Random { state } =>
+         {
+            *state ^= *state << 13;
+            *state ^= *state >> 7;
+            *state ^= *state << 17;
+            [Turn::Left, Turn::Straight, Turn::Right][(*state % 3) as usize]
+         }
+         Policy::Sequence { turns, next } =>
+         {
+            let turn = turns[*next];
+            *next = (*next + 1) % turns.len();
+            turn
+         }
+      }
+   }
+}
+
+/// parses a policy from a configuration string :
+/// `round-robin`, `random:<seed>` or `sequence:<turns>` where turns are written with L, S and R
+fn parse_policy(text: &str) -> Policy
+{
+   let mut parts = text.splitn(2, ':');
+   match (parts.next(), parts.next())
+   {
+      (Some("round-robin"), None) => Policy::RoundRobin,
+      (Some("random"), Some(seed)) =>
+      {
+         let seed: u64 = seed.parse().expect("Failed to parse the random seed.");
+         // xorshift gets stuck on a zero state
+         Policy::Random { state: seed.max(1) }
+      }
+      (Some("sequence"), Some(turns)) if !turns.is_empty() =>
+      {
+         let turns = turns.chars()
+                          .map(|c| match c
+                          {
+                             'L' => Turn::Left,
+                             'S' => Turn::Straight,
+                             'R' => Turn::Right,
+                             _ => panic!("Unrecognized turn '{}'!", c)
+                          })
+                          .collect();
+         Policy::Sequence { turns, next: 0 }
+      }
+      _ => panic!("Unrecognized policy '{}'!", text)
+   }
+}
+
+/// gives a policy to a single cart and resets its next turn accordingly
+fn set_cart_policy(traffic: &mut Traffic, id: usize, mut policy: Policy)
+{
+   let turn = policy.first_turn();
+   traffic.policies[id] = policy;
+   if let Some(cart) = traffic.carts.iter_mut().find(|cart| cart.id == id)
+   {
+      cart.turn = turn;
+   }
+}
+
+/// gives the same policy to all carts
+/// random policies are reseeded per cart so that carts do not all make the same choices
+fn set_run_policy(traffic: &mut Traffic, policy: &Policy)
+{
+   for id in 0..traffic.policies.len()
+   {
+      let policy = match policy
+      {
+         Policy::Random { state } =>
+         {
+            let seed = state.wrapping_add((id as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15));
+            Policy::Random { state: seed.max(1) }
+         }
+         policy => policy.clone()
+      };
+      set_cart_policy(traffic, id, policy);
+   }
+}
+
+//-----------------------------------------------------------------------------
+// VALIDATION
+
+impl fmt::Display for TrackError
+{
+   fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+   {
+      match self
+      {
+         TrackError::UnknownGlyph { row, col, glyph } =>
+         {
+            write!(f, "unknown glyph '{}' at ({},{})", glyph, col, row)
+         }
+         TrackError::DisconnectedCorner { row, col } =>
+         {
+            write!(f, "corner at ({},{}) is not connected on both sides", col, row)
+         }
+         TrackError::DisconnectedStraight { row, col } =>
+         {
+            write!(f, "straight road at ({},{}) is not connected at both ends", col, row)
+         }
+         TrackError::IncompleteIntersection { row, col } =>
+         {
+            write!(f, "intersection at ({},{}) does not have four arms", col, row)
+         }
+         TrackError::MisplacedCart { row, col } =>
+         {
+            write!(f, "cart at ({},{}) does not start on a compatible straight road", col, row)
+         }
+         TrackError::CartOffTrack { row, col } => write!(f, "cart at ({},{}) left the road", col, row)
+      }
+   }
+}
+
+/// returns the direction that leads back to the cell we come from
+fn opposite(direction: Direction) -> Direction
+{
+   match direction
+   {
+      Direction::Up => Direction::Down,
+      Direction::Down => Direction::Up,
+      Direction::Left => Direction::Right,
+      Direction::Right => Direction::Left
+   }
+}
+
+/// returns the vertical arm of a corner given its horizontal arm
+/// `/` joins Right and Down or Left and Up, `\` joins Left and Down or Right and Up
+fn vertical_arm(corner: char, horizontal_arm: Direction) -> Direction
+{
+   match (corner, horizontal_arm)
+   {
+      ('/', Direction::Right) | ('\\', Direction::Left) => Direction::Down,
+      _ => Direction::Up
+   }
+}
+
+/// returns true if the glyph opens toward the given direction
+/// corners open toward their horizontal arm, if they have one, and the vertical arm that goes with it
+fn opens_toward(glyph: char, arm: Option<Direction>, direction: Direction) -> bool
+{
+   match glyph
+   {
+      '/' | '\\' => arm.is_some_and(|arm| direction == arm || direction == vertical_arm(glyph, arm)),
+      '+' => true,
+      '-' | '<' | '>' => direction == Direction::Left || direction == Direction::Right,
+      '|' | '^' | 'v' => direction == Direction::Up || direction == Direction::Down,
+      _ => false
+   }
+}
+
+/// returns the position of the neighbour in the given direction, if it can be addressed
+fn neighbour(row: usize, col: usize, direction: Direction) -> Option<(usize, usize)>
+{
+   match direction
+   {
+      Direction::Up => row.checked_sub(1).map(|row| (row, col)),
+      Direction::Down => Some((row + 1, col)),
+      Direction::Left => col.checked_sub(1).map(|col| (row, col)),
+      Direction::Right => Some((row, col + 1))
+   }
+}
+
+/// finds the horizontal arm of each corner from the pieces it connects to
+/// an arm is kept only if the piece it points to opens back
+/// and the corner opens toward every piece pointing at it
+/// corners that cannot be connected either way get no arm (they open nowhere)
+fn orient_corners(grid: &[Vec<char>]) -> HashMap<(usize, usize), Option<Direction>>
+{
+   let glyph =
+      |(row, col): (usize, usize)| grid.get(row).and_then(|line| line.get(col)).cloned().unwrap_or(' ');
+   let mut arms = HashMap::new();
+   let mut pending: Vec<(usize, usize)> = Vec::new();
+   for (row, line) in grid.iter().enumerate()
+   {
+      for (col, &c) in line.iter().enumerate()
+      {
+         if c == '/' || c == '\\'
+         {
+            pending.push((row, col));
+         }
+      }
+   }
+
+   // the orientations that agree with all the neighbours that are already known
+   let candidates_of = |(row, col): (usize, usize), arms: &HashMap<(usize, usize), Option<Direction>>| {
+      let c = glyph((row, col));
+      let fits = |arm: Direction| {
+         [Direction::Up, Direction::Down, Direction::Left, Direction::Right].iter().all(|&direction| {
+            let position = neighbour(row, col, direction);
+            let opens_back = match position.map(|position| (glyph(position), arms.get(&position)))
+            {
+               None => false,
+               Some(('/', None)) | Some(('\\', None)) => return true, // not known yet
+               Some((n, n_arm)) => opens_toward(n, n_arm.cloned().flatten(), opposite(direction))
+            };
+            opens_toward(c, Some(arm), direction) == opens_back
+         })
+      };
+      [Direction::Left, Direction::Right].iter().cloned().filter(|&arm| fits(arm)).collect::<Vec<_>>()
+   };
+
+   while !pending.is_empty()
+   {
+      let mut settled = Vec::new();
+      for &position in &pending
+      {
+         match candidates_of(position, &arms).as_slice()
+         {
+            [] => settled.push((position, None)),
+            [arm] => settled.push((position, Some(*arm))),
+            _ => ()
+         }
+      }
+      // corners only surrounded by unknown corners can go either way, the first one picks an orientation
+      if settled.is_empty()
+      {
+         let position = pending[0];
+         settled.push((position, candidates_of(position, &arms).first().cloned()));
+      }
+
+      arms.extend(settled);
+      pending.retain(|position| !arms.contains_key(position));
+   }
 
-/// returns the position of the first cart it finds
-fn find_a_cart(carts: &Vehicules) -> (usize, usize)
+   arms
+}
+
+/// checks the whole track and returns all problems found, in reading order
+/// every arm of every piece should lead to a piece that opens back toward it
+fn validate_track(lines: &[String]) -> Vec<TrackError>
 {
-   for row in 0..carts.len()
+   let grid: Vec<Vec<char>> = lines.iter().map(|line| line.chars().collect()).collect();
+   let corner_arms = orient_corners(&grid);
+   let glyph =
+      |(row, col): (usize, usize)| grid.get(row).and_then(|line| line.get(col)).cloned().unwrap_or(' ');
+   let arm = |position: (usize, usize)| corner_arms.get(&position).cloned().flatten();
+
+   let mut errors = Vec::new();
+   for (row, line) in grid.iter().enumerate()
    {
-      for col in 0..carts[0].len()
+      for (col, &c) in line.iter().enumerate()
       {
-         if carts[row][col].is_some()
+         // does the cell open toward the given direction and the neighbour there open back ?
+         let connected = |direction: Direction| {
+            opens_toward(c, arm((row, col)), direction)
+            && neighbour(row, col, direction).is_some_and(|position| {
+                  opens_toward(glyph(position), arm(position), opposite(direction))
+               })
+         };
+         let horizontal = connected(Direction::Left) && connected(Direction::Right);
+         let vertical = connected(Direction::Up) && connected(Direction::Down);
+
+         match c
          {
-            return (row, col);
+            ' ' => (),
+            '-' if !horizontal => errors.push(TrackError::DisconnectedStraight { row, col }),
+            '|' if !vertical => errors.push(TrackError::DisconnectedStraight { row, col }),
+            '-' | '|' => (),
+            '/' | '\\' => match arm((row, col))
+            {
+               Some(arm) if connected(arm) && connected(vertical_arm(c, arm)) => (),
+               _ => errors.push(TrackError::DisconnectedCorner { row, col })
+            },
+            '+' if !(horizontal && vertical) => errors.push(TrackError::IncompleteIntersection { row, col }),
+            '+' => (),
+            '<' | '>' if !horizontal => errors.push(TrackError::MisplacedCart { row, col }),
+            '^' | 'v' if !vertical => errors.push(TrackError::MisplacedCart { row, col }),
+            '<' | '>' | '^' | 'v' => (),
+            glyph => errors.push(TrackError::UnknownGlyph { row, col, glyph })
          }
       }
    }
 
-   panic!("No cart left after the last collision");
+   errors
 }
 
-/// returns new coordinate produced by a direction
-fn apply_direction(direction: Direction, row: usize, col: usize) -> (usize, usize)
+//-----------------------------------------------------------------------------
+// DISPLAY
+
+/// returns the char used to display a road
+fn road_char(road: Road) -> char
+{
+   match road
+   {
+      Road::None => ' ',
+      Road::Vertical => '|',
+      Road::Horizontal => '-',
+      Road::Intersection => '+',
+      Road::LeftCorner => '/',
+      Road::RightCorner => '\\'
+   }
+}
+
+/// returns the char used to display a cart
+fn cart_char(direction: Direction) -> char
 {
    match direction
    {
-      Direction::Right => (row, col + 1),
-      Direction::Left => (row, col - 1),
-      Direction::Up => (row - 1, col),
-      Direction::Down => (row + 1, col)
+      Direction::Up => '^',
+      Direction::Down => 'v',
+      Direction::Left => '<',
+      Direction::Right => '>'
+   }
+}
+
+/// draws the track with its carts, collisions are marked with an X
+fn render(terrain: &Terrain, traffic: &Traffic, collisions: &[Collision]) -> String
+{
+   let mut grid: Vec<Vec<char>> =
+      terrain.iter().map(|row| row.iter().cloned().map(road_char).collect()).collect();
+
+   for cart in &traffic.carts
+   {
+      grid[cart.row][cart.col] = cart_char(cart.direction);
    }
+   for collision in collisions
+   {
+      grid[collision.row][collision.col] = 'X';
+   }
+
+   grid.iter().map(|row| row.iter().collect::<String>()).collect::<Vec<String>>().join("\n")
+}
+
+/// draws the track after the given number of ticks
+/// collisions that happened during the last tick are marked with an X
+fn render_at_tick(terrain: &Terrain, traffic: &Traffic, tick_number: usize) -> Result<String, TrackError>
+{
+   let mut traffic = traffic.clone();
+   let mut collisions = Vec::new();
+
+   for _ in 0..tick_number
+   {
+      collisions = one_tick(terrain, &mut traffic)?;
+   }
+
+   Ok(render(terrain, &traffic, &collisions))
+}
+
+//-----------------------------------------------------------------------------
+// TASKS
+
+/// drops crashed carts, puts the others in reading order and rebuilds the position index
+fn sort_carts(traffic: &mut Traffic)
+{
+   traffic.carts.retain(|cart| !cart.crashed);
+   traffic.carts.sort_by_key(|cart| (cart.row, cart.col));
+   traffic.positions =
+      traffic.carts.iter().enumerate().map(|(rank, cart)| ((cart.row, cart.col), rank)).collect();
+}
+
+/// returns new coordinate produced by a direction, None if it would leave the grid
+fn apply_direction(direction: Direction, row: usize, col: usize) -> Option<(usize, usize)>
+{
+   neighbour(row, col, direction)
 }
 
 /// returns the direction after a right turn
@@ -152,7 +616,8 @@ fn turn_left(direction: Direction) -> Direction
 }
 
 /// returns a cart after aplying any terrain related effect
-fn apply_terrain(direction: Direction, turn: Turn, road: Road) -> (Direction, Turn)
+/// the policy decides the turn that will be taken at the next intersection
+fn apply_terrain(direction: Direction, turn: Turn, road: Road, policy: &mut Policy) -> (Direction, Turn)
 {
    match road
    {
@@ -183,94 +648,405 @@ fn apply_terrain(direction: Direction, turn: Turn, road: Road) -> (Direction, Tu
       },
       Road::Intersection => match turn
       {
-         Turn::Straight => (direction, Turn::Right),
-         Turn::Right => (turn_right(direction), Turn::Left),
-         Turn::Left => (turn_left(direction), Turn::Straight)
+         Turn::Straight => (direction, policy.next_turn(turn)),
+         Turn::Right => (turn_right(direction), policy.next_turn(turn)),
+         Turn::Left => (turn_left(direction), policy.next_turn(turn))
       }
    }
 }
 
 /// plays one tick and returns a vector of all collisions that happened during this tick in order
-fn one_tick(tick_number: usize, terrain: &Terrain, carts: &mut Vehicules) -> Vec<(usize, usize)>
+/// carts move in reading order (row by row, then column by column)
+/// fails if a cart leaves the road
+fn one_tick(terrain: &Terrain, traffic: &mut Traffic) -> Result<Vec<Collision>, TrackError>
 {
    let mut collisions = Vec::new();
 
-   for row in 0..carts.len()
+   for rank in 0..traffic.carts.len()
    {
-      for col in 0..carts[0].len()
+      let cart = traffic.carts[rank];
+      if cart.crashed
       {
-         match carts[row][col]
-         {
-            Some(cart) if cart.last_tick == tick_number =>
-            {
-               let (new_row, new_col) = apply_direction(cart.direction, row, col);
-               if carts[new_row][new_col].is_none()
-               {
-                  let (direction, turn) = apply_terrain(cart.direction, cart.turn, terrain[new_row][new_col]);
-                  let new_cart = Cart { direction: direction, turn: turn, last_tick: tick_number + 1 };
-                  carts[new_row][new_col] = Some(new_cart);
-               }
-               else
-               {
-                  collisions.push((new_row, new_col));
-                  carts[new_row][new_col] = None;
-               }
-               carts[row][col] = None;
-            }
-            _ => ()
-         }
+         continue;
+      }
+
+      traffic.positions.remove(&(cart.row, cart.col));
+      let on_road = |&(row, col): &(usize, usize)| {
+         terrain.get(row).and_then(|line| line.get(col)).is_some_and(|&road| road != Road::None)
+      };
+      let (row, col) = apply_direction(cart.direction, cart.row, cart.col)
+         .filter(on_road)
+         .ok_or(TrackError::CartOffTrack { row: cart.row, col: cart.col })?;
+      if let Some(hit_rank) = traffic.positions.remove(&(row, col))
+      {
+         let hit_cart = &mut traffic.carts[hit_rank];
+         hit_cart.crashed = true;
+         collisions.push(Collision { row, col, moving_cart: cart.id, hit_cart: hit_cart.id });
+         traffic.carts[rank] = Cart { row, col, crashed: true, ..cart };
+      }
+      else
+      {
+         let (direction, turn) =
+            apply_terrain(cart.direction, cart.turn, terrain[row][col], &mut traffic.policies[cart.id]);
+         traffic.carts[rank] = Cart { row, col, direction, turn, ..cart };
+         traffic.positions.insert((row, col), rank);
       }
    }
 
-   collisions
+   sort_carts(traffic);
+   Ok(collisions)
 }
 
-/// returns the location of the first collision
-fn first_collision(terrain: &Terrain, carts: &Vehicules) -> (usize, usize)
+/// runs the carts until at most one is left (or max_ticks have been played)
+/// and returns the timeline of all collisions
+fn run_timeline(terrain: &Terrain, traffic: &Traffic, max_ticks: usize) -> Result<Timeline, TrackError>
 {
+   let mut traffic = traffic.clone();
+   let mut ticks = Vec::new();
    let mut tick_number = 0;
-   let mut carts = carts.to_vec();
-   let mut collisions = one_tick(tick_number, &terrain, &mut carts);
 
-   while collisions.is_empty()
+   while traffic.carts.len() > 1 && tick_number < max_ticks
    {
+      let collisions = one_tick(terrain, &mut traffic)?;
+      if !collisions.is_empty()
+      {
+         ticks.push(TickEvents { tick: tick_number, collisions });
+      }
       tick_number += 1;
-      collisions = one_tick(tick_number, &terrain, &mut carts);
    }
 
-   collisions[0]
+   // the survivor, if any, is reported at the end of the tick that removed the other carts
+   let survivor = match traffic.carts.as_slice()
+   {
+      [cart] =>
+      {
+         let tick = tick_number.checked_sub(1);
+         Some(Survivor { id: cart.id, row: cart.row, col: cart.col, tick })
+      }
+      _ => None
+   };
+   Ok(Timeline { ticks, survivor })
 }
 
-/// returns the position of the last cart standing after all the other collisions
-fn last_cart(terrain: &Terrain, carts: &Vehicules) -> (usize, usize)
+/// returns the location of the first collision, if there was one
+fn first_collision(timeline: &Timeline) -> Option<(usize, usize)>
 {
-   let mut cart_number = carts.iter().flat_map(|row| row).filter(|cart| cart.is_some()).count();
-   let mut carts = carts.to_vec();
-   let mut tick_number = 0;
+   timeline.ticks.first().map(|events| (events.collisions[0].row, events.collisions[0].col))
+}
 
-   while cart_number > 1
+/// returns the position of the last cart standing after all the other collisions, if there is one
+fn last_cart(timeline: &Timeline) -> Option<(usize, usize)>
+{
+   timeline.survivor.as_ref().map(|survivor| (survivor.row, survivor.col))
+}
+
+/// exports a timeline as JSON, coordinates are given as x (column) and y (row)
+fn timeline_to_json(timeline: &Timeline) -> String
+{
+   let ticks: Vec<String> =
+      timeline.ticks
+              .iter()
+              .map(|events| {
+                 let collisions: Vec<String> =
+                    events.collisions
+                          .iter()
+                          .map(|c| {
+                             format!("{{\"x\":{},\"y\":{},\"moving_cart\":{},\"hit_cart\":{}}}",
+                                     c.col, c.row, c.moving_cart, c.hit_cart)
+                          })
+                          .collect();
+                 format!("{{\"tick\":{},\"collisions\":[{}]}}", events.tick, collisions.join(","))
+              })
+              .collect();
+
+   let survivor = match &timeline.survivor
    {
-      let collisions = one_tick(tick_number, &terrain, &mut carts);
-      cart_number -= 2 * collisions.len();
-      tick_number += 1;
-   }
+      Some(s) =>
+      {
+         let tick = s.tick.map_or("null".to_string(), |tick| tick.to_string());
+         format!("{{\"cart\":{},\"x\":{},\"y\":{},\"tick\":{}}}", s.id, s.col, s.row, tick)
+      }
+      None => "null".to_string()
+   };
 
-   find_a_cart(&carts)
+   format!("{{\"ticks\":[{}],\"survivor\":{}}}", ticks.join(","), survivor)
 }
 
 //-----------------------------------------------------------------------------
 // MAIN
 
+/// returns the argument that follows the given flag on the command line
+fn flag_value(flag: &str) -> Option<String>
+{
+   let args: Vec<String> = std::env::args().collect();
+   args.windows(2).find(|pair| pair[0] == flag).map(|pair| pair[1].clone())
+}
+
 fn main()
 {
    let input_path = "./data/input.txt";
-   let (terrain, carts) = input_data(input_path);
+   let lines = input_data(input_path);
+
+   // validation
+   let errors = validate_track(&lines);
+   if !errors.is_empty()
+   {
+      errors.iter().for_each(|error| println!("invalid track: {}", error));
+      return;
+   }
+
+   let (terrain, traffic) = parse_track(&lines);
+   let timeline = match run_timeline(&terrain, &traffic, usize::MAX)
+   {
+      Ok(timeline) => timeline,
+      Err(error) =>
+      {
+         println!("invalid track: {}", error);
+         return;
+      }
+   };
 
    // task1
-   let (yfirst, xfirst) = first_collision(&terrain, &carts);
-   println!("first collision ({},{})", xfirst, yfirst);
+   match first_collision(&timeline)
+   {
+      Some((yfirst, xfirst)) => println!("first collision ({},{})", xfirst, yfirst),
+      None => println!("no collision")
+   }
 
    // task2
-   let (ylast, xlast) = last_cart(&terrain, &carts);
-   println!("last cart ({},{})", xlast, ylast);
+   match last_cart(&timeline)
+   {
+      Some((ylast, xlast)) => println!("last cart ({},{})", xlast, ylast),
+      None => println!("no cart left")
+   }
+
+   // timeline
+   println!("timeline {}", timeline_to_json(&timeline));
+
+   // map after the given number of ticks
+   if let Some(tick_number) = flag_value("--render")
+   {
+      let tick_number: usize = tick_number.parse().expect("Failed to parse the number of ticks.");
+      match render_at_tick(&terrain, &traffic, tick_number)
+      {
+         Ok(map) => println!("map after {} ticks:\n{}", tick_number, map),
+         Err(error) => println!("invalid track: {}", error)
+      }
+   }
+
+   // what-if experiment with another intersection policy
+   if let Some(config) = flag_value("--policy")
+   {
+      let mut traffic = traffic.clone();
+      set_run_policy(&mut traffic, &parse_policy(&config));
+      match run_timeline(&terrain, &traffic, 100_000)
+      {
+         Ok(timeline) =>
+         {
+            let survivor = match &timeline.survivor
+            {
+               Some(survivor) => format!("cart {} at ({},{})", survivor.id, survivor.col, survivor.row),
+               None => "none".to_string()
+            };
+            println!("policy {} : {} collision ticks, survivor {}", config, timeline.ticks.len(), survivor);
+         }
+         Err(error) => println!("policy {} : {}", config, error)
+      }
+   }
+}
+
+//-----------------------------------------------------------------------------
+// TESTS
+
+#[cfg(test)]
+mod tests
+{
+   use super::*;
+
+   /// builds the track from its lines and runs it to completion
+   fn timeline_of(lines: &[&str]) -> Timeline
+   {
+      let lines: Vec<String> = lines.iter().map(|line| line.to_string()).collect();
+      assert!(validate_track(&lines).is_empty(), "Invalid track!");
+      let (terrain, traffic) = parse_track(&lines);
+      run_timeline(&terrain, &traffic, 1000).expect("Cart off track!")
+   }
+
+   #[test]
+   fn single_cart_survives_at_start()
+   {
+      let timeline = timeline_of(&["/->-\\", "|   |", "\\---/"]);
+      assert!(timeline.ticks.is_empty());
+      assert_eq!(first_collision(&timeline), None);
+      assert_eq!(last_cart(&timeline), Some((0, 2)));
+      assert_eq!(timeline.survivor.map(|survivor| survivor.tick), Some(None));
+   }
+
+   #[test]
+   fn no_collision_without_carts()
+   {
+      let timeline = timeline_of(&["/---\\", "|   |", "\\---/"]);
+      assert_eq!(first_collision(&timeline), None);
+      assert_eq!(last_cart(&timeline), None);
+   }
+
+   #[test]
+   fn test_track_collides()
+   {
+      let lines = input_data("./data/test.txt");
+      let (terrain, traffic) = parse_track(&lines);
+      let timeline = run_timeline(&terrain, &traffic, 1000).expect("Cart off track!");
+      assert_eq!(first_collision(&timeline), Some((0, 2)));
+      assert_eq!(last_cart(&timeline), Some((4, 6)));
+      assert_eq!(timeline.survivor.map(|survivor| survivor.tick), Some(Some(2)));
+   }
+
+   /// validates a track given as lines
+   fn errors_of(lines: &[&str]) -> Vec<TrackError>
+   {
+      let lines: Vec<String> = lines.iter().map(|line| line.to_string()).collect();
+      validate_track(&lines)
+   }
+
+   #[test]
+   fn validation_errors()
+   {
+      assert!(errors_of(&["#"]) == vec![TrackError::UnknownGlyph { row: 0, col: 0, glyph: '#' }]);
+      assert!(errors_of(&["/"]) == vec![TrackError::DisconnectedCorner { row: 0, col: 0 }]);
+      assert!(errors_of(&["|"]) == vec![TrackError::DisconnectedStraight { row: 0, col: 0 }]);
+      assert!(errors_of(&["+"]) == vec![TrackError::IncompleteIntersection { row: 0, col: 0 }]);
+      assert!(errors_of(&["^"]) == vec![TrackError::MisplacedCart { row: 0, col: 0 }]);
+
+      // dead-end straights would let the carts leave the road
+      let expected = vec![TrackError::DisconnectedStraight { row: 0, col: 0 },
+                          TrackError::DisconnectedStraight { row: 0, col: 4 },
+                          TrackError::DisconnectedStraight { row: 1, col: 0 },
+                          TrackError::DisconnectedStraight { row: 1, col: 4 }];
+      assert!(errors_of(&["-->--", "->---"]) == expected);
+      assert!(errors_of(&["/->-\\", "|   |", "\\-<-/"]).is_empty());
+      assert!(errors_of(&["/\\", "\\/"]).is_empty());
+
+      // corners only connect through their two arms, whatever the corners around them
+      let expected = vec![TrackError::DisconnectedCorner { row: 0, col: 2 },
+                          TrackError::MisplacedCart { row: 1, col: 1 },
+                          TrackError::DisconnectedCorner { row: 1, col: 2 },
+                          TrackError::DisconnectedCorner { row: 1, col: 3 }];
+      assert!(errors_of(&["/>\\", "\\<\\/"]) == expected);
+   }
+
+   #[test]
+   fn carts_leaving_the_grid_fail()
+   {
+      let lines: Vec<String> = ["/>\\", "\\<\\/"].iter().map(|line| line.to_string()).collect();
+      let (terrain, traffic) = parse_track(&lines);
+      assert!(run_timeline(&terrain, &traffic, 1000).is_err());
+      assert_eq!(apply_direction(Direction::Up, 0, 3), None);
+      assert_eq!(apply_direction(Direction::Left, 3, 0), None);
+   }
+
+   #[test]
+   fn render_marks_collisions()
+   {
+      let lines = input_data("./data/test.txt");
+      let (terrain, traffic) = parse_track(&lines);
+      assert_eq!(render_at_tick(&terrain, &traffic, 0).expect("Cart off track!"), lines.join("\n"));
+
+      let map = render_at_tick(&terrain, &traffic, 1).expect("Cart off track!");
+      let rows: Vec<Vec<char>> = map.lines().map(|row| row.chars().collect()).collect();
+      assert_eq!(rows.len(), lines.len());
+      let markers: Vec<(usize, usize)> = rows.iter()
+                                             .enumerate()
+                                             .flat_map(|(row, line)| {
+                                                line.iter()
+                                                    .enumerate()
+                                                    .filter(|&(_, &c)| c == 'X')
+                                                    .map(move |(col, _)| (row, col))
+                                             })
+                                             .collect();
+      let timeline = run_timeline(&terrain, &traffic, 1).expect("Cart off track!");
+      let expected: Vec<(usize, usize)> =
+         timeline.ticks[0].collisions.iter().map(|collision| (collision.row, collision.col)).collect();
+      assert_eq!(markers, expected);
+   }
+
+   #[test]
+   fn policies_are_parsed()
+   {
+      assert!(parse_policy("round-robin") == Policy::RoundRobin);
+      assert!(parse_policy("random:42") == Policy::Random { state: 42 });
+      assert!(parse_policy("random:0") == Policy::Random { state: 1 });
+      let turns = vec![Turn::Left, Turn::Straight, Turn::Right];
+      assert!(parse_policy("sequence:LSR") == Policy::Sequence { turns, next: 0 });
+   }
+
+   #[test]
+   fn sequence_wraps_around()
+   {
+      let mut policy = parse_policy("sequence:LR");
+      let mut turn = policy.first_turn();
+      let mut turns = vec![turn];
+      for _ in 0..4
+      {
+         turn = policy.next_turn(turn);
+         turns.push(turn);
+      }
+      assert!(turns == vec![Turn::Left, Turn::Right, Turn::Left, Turn::Right, Turn::Left]);
+   }
+
+   #[test]
+   fn round_robin_sequence_matches_default()
+   {
+      let lines = input_data("./data/input.txt");
+      let (terrain, traffic) = parse_track(&lines);
+      let mut sequence_traffic = traffic.clone();
+      set_run_policy(&mut sequence_traffic, &parse_policy("sequence:LSR"));
+      let timeline = run_timeline(&terrain, &traffic, 100_000).expect("Cart off track!");
+      let sequence_timeline = run_timeline(&terrain, &sequence_traffic, 100_000).expect("Cart off track!");
+      assert_eq!(timeline_to_json(&sequence_timeline), timeline_to_json(&timeline));
+   }
+
+   #[test]
+   fn random_policies_are_reseeded_per_cart()
+   {
+      let lines = input_data("./data/input.txt");
+      let (_, mut traffic) = parse_track(&lines);
+      set_run_policy(&mut traffic, &parse_policy("random:42"));
+      let mut states: Vec<u64> = traffic.policies
+                                        .iter()
+                                        .map(|policy| match policy
+                                        {
+                                           Policy::Random { state } => *state,
+                                           _ => panic!("Every cart should follow a random policy!")
+                                        })
+                                        .collect();
+      assert!(states.iter().all(|&state| state != 0));
+      let nb_carts = states.len();
+      states.sort_unstable();
+      states.dedup();
+      assert_eq!(states.len(), nb_carts, "Two carts share the same seed!");
+   }
+
+   #[test]
+   fn cart_policy_leaves_other_carts_unchanged()
+   {
+      let lines = input_data("./data/input.txt");
+      let (_, traffic) = parse_track(&lines);
+      let mut changed_traffic = traffic.clone();
+      set_cart_policy(&mut changed_traffic, 1, parse_policy("sequence:R"));
+
+      for (cart, changed_cart) in traffic.carts.iter().zip(changed_traffic.carts.iter())
+      {
+         let policy = &changed_traffic.policies[cart.id];
+         if cart.id == 1
+         {
+            assert!(*policy == Policy::Sequence { turns: vec![Turn::Right], next: 0 });
+            assert!(changed_cart.turn == Turn::Right);
+         }
+         else
+         {
+            assert!(*policy == Policy::RoundRobin);
+            assert!(changed_cart == cart);
+         }
+      }
+   }
 }
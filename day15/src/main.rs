@@ -1,5 +1,5 @@
 use std::fs::File;
-use std::io::{BufRead, BufReader};
+use std::io::{BufRead, BufReader, Write};
 
 //-----------------------------------------------------------------------------
 // TYPE
@@ -30,6 +30,15 @@ enum Cell
 
 type Map = [Vec<Cell>];
 
+/// full state of a battle, enough to pause it and resume it later
+#[derive(Clone, PartialEq)]
+struct Battle
+{
+   map: Vec<Vec<Cell>>,
+   elf_attack: i32,
+   round: i32
+}
+
 //-----------------------------------------------------------------------------
 // INPUT
 
@@ -305,10 +314,14 @@ fn round(mut map: &mut Map, elf_attack: i32) -> Round
 }
 
 /// plays the game until a side win and returns the number of turn played
-fn simulate(mut map: &mut Map, elf_attack: i32) -> i32
+fn simulate(map: &mut Map, elf_attack: i32) -> i32
 {
-   let mut turn = 0;
+   simulate_from(map, elf_attack, 0)
+}
 
+/// plays the game, starting at the given turn, until a side win and returns the number of turn played
+fn simulate_from(mut map: &mut Map, elf_attack: i32, mut turn: i32) -> i32
+{
    loop
    {
       //display(&map, turn);
@@ -323,6 +336,140 @@ fn simulate(mut map: &mut Map, elf_attack: i32) -> i32
    }
 }
 
+//-----------------------------------------------------------------------------
+// SNAPSHOT
+
+/// plays full rounds until the battle reaches the given round
+/// returns NoMoreTarget if the battle ended before (the battle is then left at its final round)
+fn advance(battle: &mut Battle, target_round: i32) -> Round
+{
+   while battle.round < target_round
+   {
+      if round(&mut battle.map, battle.elf_attack) == Round::NoMoreTarget
+      {
+         return Round::NoMoreTarget;
+      }
+      battle.round += 1;
+   }
+
+   Round::NormalRound
+}
+
+/// resumes a battle until a side win and returns the number of turn played since the start of the battle
+fn resume(battle: &mut Battle) -> i32
+{
+   battle.round = simulate_from(&mut battle.map, battle.elf_attack, battle.round);
+   battle.round
+}
+
+/// turns a battle into text
+/// the map is written as in the input followed by one line per unit in reading order:
+/// `unit row col kind hp attack turn`
+fn snapshot_to_string(battle: &Battle) -> String
+{
+   let mut result = format!("round {}\nelf_attack {}\n", battle.round, battle.elf_attack);
+
+   for row in &battle.map
+   {
+      for cell in row
+      {
+         result.push(match cell
+         {
+            Cell::Wall => '#',
+            Cell::Empty => '.',
+            Cell::Unit(unit) if unit.kind == Kind::Elf => 'E',
+            Cell::Unit(_) => 'G'
+         });
+      }
+      result.push('\n');
+   }
+
+   for (row, col, unit) in list_units(&battle.map)
+   {
+      let kind = if unit.kind == Kind::Elf { 'E' } else { 'G' };
+      result += &format!("unit {} {} {} {} {} {}\n", row, col, kind, unit.hp, unit.attack, unit.turn);
+   }
+
+   result
+}
+
+/// rebuilds a battle from its text representation
+fn snapshot_from_string(text: &str) -> Battle
+{
+   /// parses the value that follows a given key
+   fn parse_field(line: Option<&str>, key: &str) -> i32
+   {
+      let line = line.expect("Truncated snapshot!");
+      let mut words = line.split_whitespace();
+      if words.next() != Some(key)
+      {
+         panic!("Expected '{}' in snapshot, found '{}'!", key, line);
+      }
+      words.next().and_then(|value| value.parse().ok()).expect("Failed to parse snapshot field.")
+   }
+
+   let mut lines = text.lines();
+   let round = parse_field(lines.next(), "round");
+   let elf_attack = parse_field(lines.next(), "elf_attack");
+
+   let mut map = Vec::new();
+   let mut unit_count = 0;
+   for line in lines
+   {
+      if !line.starts_with("unit")
+      {
+         map.push(parse_line(line));
+         continue;
+      }
+
+      let words: Vec<&str> = line.split_whitespace().collect();
+      if words.len() != 7
+      {
+         panic!("Malformed unit line '{}'!", line);
+      }
+      let parse = |word: &str| word.parse::<i64>().expect("Failed to parse unit field.");
+      let (row, col) = (parse(words[1]) as usize, parse(words[2]) as usize);
+      let kind = match words[3]
+      {
+         "E" => Kind::Elf,
+         "G" => Kind::Gobelin,
+         _ => panic!("Unrecognized unit kind '{}'!", words[3])
+      };
+      match map.get_mut(row).and_then(|r: &mut Vec<Cell>| r.get_mut(col))
+      {
+         Some(Cell::Unit(unit)) if unit.kind == kind =>
+         {
+            unit.hp = parse(words[4]) as i32;
+            unit.attack = parse(words[5]) as i32;
+            unit.turn = parse(words[6]) as usize;
+            unit_count += 1;
+         }
+         _ => panic!("Unit line '{}' does not match the map!", line)
+      }
+   }
+
+   if unit_count != list_units(&map).len()
+   {
+      panic!("Snapshot does not describe every unit on the map!");
+   }
+
+   Battle { map, elf_attack, round }
+}
+
+/// writes a snapshot of the battle to a file
+fn save_snapshot(battle: &Battle, path: &str)
+{
+   let mut file = File::create(path).expect("Failed to create snapshot file.");
+   file.write_all(snapshot_to_string(battle).as_bytes()).expect("Failed to write snapshot.");
+}
+
+/// loads a snapshot of the battle from a file
+fn load_snapshot(path: &str) -> Battle
+{
+   let text = std::fs::read_to_string(path).expect("Failed to open snapshot file.");
+   snapshot_from_string(&text)
+}
+
 //-----------------------------------------------------------------------------
 // TASK2
 
@@ -381,6 +528,13 @@ fn find_optimal_attack(map: &Map, mut base_attack: i32) -> i32
 //-----------------------------------------------------------------------------
 // MAIN
 
+/// returns the argument that follows the given flag on the command line
+fn flag_value(flag: &str) -> Option<String>
+{
+   let args: Vec<String> = std::env::args().collect();
+   args.windows(2).find(|pair| pair[0] == flag).map(|pair| pair[1].clone())
+}
+
 fn main()
 {
    let input_path = "./data/input.txt";
@@ -397,6 +551,24 @@ fn main()
       println!("score final : {} (total_hp:{} turn_number:{})", score, total_hp, turn_number);
    }
 
+   // snapshots are optional, `--snapshot PATH` pauses the battle midway and `--resume PATH` finishes it
+   if let Some(path) = flag_value("--snapshot")
+   {
+      let mut battle = Battle { map: clone_map(&map), elf_attack, round: 0 };
+      let final_turn_number = simulate(&mut clone_map(&map), elf_attack);
+      advance(&mut battle, final_turn_number / 2);
+      save_snapshot(&battle, &path);
+      println!("snapshot at round {} saved to {}", battle.round, path);
+   }
+   if let Some(path) = flag_value("--resume")
+   {
+      let mut battle = load_snapshot(&path);
+      let turn_number = resume(&mut battle);
+      let total_hp = sum_hp(&battle.map);
+      let score = turn_number * total_hp;
+      println!("score resumed : {} (total_hp:{} turn_number:{})", score, total_hp, turn_number);
+   }
+
    // task2
    {
       let mut map = clone_map(&map);
@@ -409,3 +581,45 @@ fn main()
       println!("score optimal : {} (total_hp:{} turn_number:{})", score, total_hp, turn_number);
    }
 }
+
+//-----------------------------------------------------------------------------
+// TESTS
+
+#[cfg(test)]
+mod tests
+{
+   use super::*;
+
+   /// pauses the battle at various rounds, goes through a snapshot and checks that resuming changes nothing
+   fn check_resume(path: &str, elf_attack: i32)
+   {
+      let map = input_data(path);
+      let mut final_map = clone_map(&map);
+      let turn_number = simulate(&mut final_map, elf_attack);
+
+      for paused_round in [0, 1, turn_number / 2, turn_number - 1].iter().cloned()
+      {
+         let mut battle = Battle { map: clone_map(&map), elf_attack, round: 0 };
+         advance(&mut battle, paused_round);
+         let text = snapshot_to_string(&battle);
+         let mut resumed = snapshot_from_string(&text);
+         assert!(resumed == battle, "Snapshot round-trip altered the battle at round {}!", paused_round);
+         assert_eq!(snapshot_to_string(&resumed), text);
+         assert_eq!(resume(&mut resumed), turn_number);
+         assert!(resumed.map == final_map, "Resumed battle diverged at round {}!", paused_round);
+      }
+   }
+
+   #[test]
+   fn snapshot_resumes_test()
+   {
+      check_resume("./data/test.txt", 3);
+   }
+
+   #[test]
+   fn snapshot_resumes_input()
+   {
+      check_resume("./data/input.txt", 3);
+      check_resume("./data/input.txt", 20);
+   }
+}
@@ -0,0 +1,126 @@
+use crate::{fill_container, is_free, is_solid, Map, Material};
+
+//-----------------------------------------------------------------------------
+// TYPE
+
+/// a row of a basin that filled with stable water
+pub struct Fill
+{
+   pub tick: usize,
+   pub y: usize,
+   pub xmin: usize,
+   pub xmax: usize
+}
+
+/// water that moves one square per tick
+/// falling water goes down, water on solid ground spreads to the sides and closed rows fill up
+pub struct Flow
+{
+   pub map: Map,
+   pub tick: usize,
+   /// every row that filled so far, in order
+   pub fills: Vec<Fill>,
+   springs: Vec<(usize, usize)>,
+   /// falling water that might move during the next tick
+   active: Vec<(usize, usize)>
+}
+
+//-----------------------------------------------------------------------------
+// FLOW
+
+impl Flow
+{
+   /// all the springs start flowing at tick 0
+   pub fn new(springs: &[(usize, usize)], mut map: Map) -> Flow
+   {
+      let mut active = Vec::new();
+      for &(x, y) in springs
+      {
+         if is_free(x, y, &map)
+         {
+            map.set(x, y, Material::FallingWater);
+            active.push((x, y));
+         }
+      }
+      Flow { map, tick: 0, fills: Vec::new(), springs: springs.to_vec(), active }
+   }
+
+   /// true once no water can move anymore
+   pub fn is_settled(&self) -> bool
+   {
+      self.active.is_empty()
+   }
+
+   /// moves every active water square by one square, returns the rows filled during the tick
+   pub fn step(&mut self) -> &[Fill]
+   {
+      self.tick += 1;
+      let nb_fills = self.fills.len();
+      let active = std::mem::take(&mut self.active);
+
+      for (x, y) in active
+      {
+         if self.map.get(x, y) != Some(Material::FallingWater)
+         {
+            // the square has been turned into flowing or stable water during this tick
+            continue;
+         }
+
+         if is_free(x, y + 1, &self.map)
+         {
+            // falls
+            self.map.set(x, y + 1, Material::FallingWater);
+            self.active.push((x, y + 1));
+         }
+         else if is_solid(x, y + 1, &self.map)
+         {
+            // spreads to the sides
+            self.map.set(x, y, Material::FlowingWater);
+            for side in [x + 1, x - 1]
+            {
+               if is_free(side, y, &self.map)
+               {
+                  self.map.set(side, y, Material::FallingWater);
+                  self.active.push((side, y));
+               }
+            }
+
+            // fills the row if it is closed, the water falling on it can now spread
+            if let Some((xmin, xmax)) = fill_container(x, y, &mut self.map)
+            {
+               self.fills.push(Fill { tick: self.tick, y, xmin, xmax });
+               if y > 0
+               {
+                  let map = &self.map;
+                  let above = (xmin..=xmax).filter(|&x| map.get(x, y - 1) == Some(Material::FallingWater));
+                  self.active.extend(above.map(|x| (x, y - 1)));
+               }
+            }
+         }
+      }
+
+      if self.is_settled()
+      {
+         for &(x, y) in &self.springs
+         {
+            if self.map.get(x, y).is_some()
+            {
+               self.map.set(x, y, Material::Source);
+            }
+         }
+      }
+
+      &self.fills[nb_fills..]
+   }
+
+   /// runs until the water is settled, calling `on_tick` after each tick
+   pub fn run<F>(&mut self, mut on_tick: F)
+      where F: FnMut(&Flow)
+   {
+      while !self.is_settled()
+      {
+         self.step();
+         on_tick(self);
+      }
+   }
+}
@@ -0,0 +1,115 @@
+use crate::{Map, Material};
+use std::fs::File;
+use std::io::Write;
+
+//-----------------------------------------------------------------------------
+// TYPE
+
+/// part of the map that is exported, in true coordinates (bounds included)
+pub struct Crop
+{
+   pub xmin: usize,
+   pub xmax: usize,
+   pub ymin: usize,
+   pub ymax: usize
+}
+
+/// writes a frame of the map every `every` updates
+pub struct FrameWriter
+{
+   directory: String,
+   scale: usize,
+   crop: Option<Crop>,
+   every: usize,
+   nb_updates: usize,
+   nb_frames: usize
+}
+
+//-----------------------------------------------------------------------------
+// IMAGE
+
+/// takes a material and outputs the corresponding rgb colour
+fn colour_of_material(m: Material) -> [u8; 3]
+{
+   match m
+   {
+      Material::Clay => [130, 80, 40],
+      Material::Sand => [240, 220, 170],
+      Material::Source => [230, 30, 30],
+      Material::Water => [20, 60, 200],
+      Material::FallingWater => [120, 190, 255],
+      Material::FlowingWater => [40, 150, 240]
+   }
+}
+
+/// writes the map as a binary PPM image, each cell being a square of `scale` pixels
+/// the crop window, if any, is clipped to the map
+pub fn write_ppm(path: &str, map: &Map, scale: usize, crop: Option<&Crop>)
+{
+   let map_xmax = map.xmin + map.cells[0].len() - 1;
+   let map_ymax = map.cells.len() - 1;
+   let (xmin, xmax, ymin, ymax) = match crop
+   {
+      Some(crop) => (std::cmp::max(crop.xmin, map.xmin),
+                     std::cmp::min(crop.xmax, map_xmax),
+                     crop.ymin,
+                     std::cmp::min(crop.ymax, map_ymax)),
+      None => (map.xmin, map_xmax, 0, map_ymax)
+   };
+   if xmin > xmax || ymin > ymax
+   {
+      panic!("The crop window does not intersect the map!");
+   }
+
+   let mut file = File::create(path).expect("Failed to create image file.");
+   let (width, height) = ((xmax - xmin + 1) * scale, (ymax - ymin + 1) * scale);
+   write!(file, "P6\n{} {}\n255\n", width, height).expect("Failed to write header.");
+
+   for row in &map.cells[ymin..=ymax]
+   {
+      let cells = &row[xmin - map.xmin..=xmax - map.xmin];
+      let line: Vec<u8> = cells.iter()
+                               .flat_map(|&m| std::iter::repeat_n(colour_of_material(m), scale))
+                               .flatten()
+                               .collect();
+      for _ in 0..scale
+      {
+         file.write_all(&line).expect("Failed to write image.");
+      }
+   }
+}
+
+//-----------------------------------------------------------------------------
+// ANIMATION
+
+impl FrameWriter
+{
+   /// frames will be written in the given directory as frame_00000.ppm, frame_00001.ppm...
+   pub fn new(directory: &str, scale: usize, crop: Option<Crop>, every: usize) -> FrameWriter
+   {
+      std::fs::create_dir_all(directory).expect("Failed to create the frame directory.");
+      FrameWriter { directory: directory.to_string(), scale, crop, every, nb_updates: 0, nb_frames: 0 }
+   }
+
+   /// to be called on each update of the map
+   pub fn observe(&mut self, map: &Map)
+   {
+      if self.nb_updates.is_multiple_of(self.every)
+      {
+         let path = format!("{}/frame_{:05}.ppm", self.directory, self.nb_frames);
+         write_ppm(&path, map, self.scale, self.crop.as_ref());
+         self.nb_frames += 1;
+      }
+      self.nb_updates += 1;
+   }
+
+   pub fn nb_frames(&self) -> usize
+   {
+      self.nb_frames
+   }
+
+   pub fn directory(&self) -> &str
+   {
+      &self.directory
+   }
+}
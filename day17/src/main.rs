@@ -1,5 +1,9 @@
 #[macro_use]
 extern crate scan_fmt;
+mod flow;
+mod image;
+use flow::Flow;
+use image::{write_ppm, Crop, FrameWriter};
 use std::fs::File;
 use std::io::{BufRead, BufReader};
 
@@ -25,7 +29,29 @@ enum Material
    Clay
 }
 
-type Map = [Vec<Material>];
+/// the ground, cell (x, y) is stored in `cells[y][x - xmin]` so that the map is addressed in true coordinates
+#[derive(Clone)]
+struct Map
+{
+   xmin: usize,
+   /// the rows covered by clay, only those are counted
+   ymin: usize,
+   ymax: usize,
+   cells: Vec<Vec<Material>>
+}
+
+/// what a spring added to the map (on the counted rows)
+/// water stops on the water of the previous springs so a spring placed in it, such as a duplicate,
+/// adds nothing : no wet or stable square and no deepest row
+struct Reach
+{
+   x: usize,
+   y: usize,
+   nb_wet: usize,
+   nb_settled: usize,
+   /// deepest row wet by this spring
+   ydeepest: Option<usize>
+}
 
 //-----------------------------------------------------------------------------
 // INPUT
@@ -60,30 +86,49 @@ fn input_data(path: &str) -> Vec<Interval>
 //-----------------------------------------------------------------------------
 // SIDE FUNCTIONS
 
-/// takes interval and the position of a source to build a map
-fn fill_map(xsource: usize, ysource: usize, intervals: &[Interval]) -> (usize, usize, Vec<Vec<Material>>)
+/// takes the intervals and the position of the springs to build a map
+/// the map goes from row 0 to the last clay row and has a column of sand on both sides
+fn fill_map(springs: &[(usize, usize)], intervals: &[Interval]) -> Map
 {
-   let xmin = std::cmp::min(xsource, intervals.iter().map(|i| i.xmin).min().unwrap()) - 1;
-   let xmax = intervals.iter().map(|i| i.xmax).max().unwrap() - xmin + 1;
-
+   let xmin = intervals.iter().map(|i| i.xmin).chain(springs.iter().map(|&(x, _)| x)).min().unwrap() - 1;
+   let xmax = intervals.iter().map(|i| i.xmax).chain(springs.iter().map(|&(x, _)| x)).max().unwrap() + 1;
    let ymin = intervals.iter().map(|i| i.ymin).min().unwrap();
-   let ymax = intervals.iter().map(|i| i.ymax).max().unwrap() - ymin;
-   let mut map = vec![vec![Material::Sand; xmax + 1]; ymax + 1];
+   let ymax = intervals.iter().map(|i| i.ymax).max().unwrap();
+   let mut map = Map { xmin, ymin, ymax, cells: vec![vec![Material::Sand; xmax - xmin + 1]; ymax + 1] };
 
    for interval in intervals
    {
-      for y_unscaled in interval.ymin..=interval.ymax
+      for y in interval.ymin..=interval.ymax
       {
-         let y = y_unscaled - ymin;
-         for x_unscaled in interval.xmin..=interval.xmax
+         for x in interval.xmin..=interval.xmax
          {
-            let x = x_unscaled - xmin;
-            map[y][x] = Material::Clay;
+            map.set(x, y, Material::Clay);
          }
       }
    }
 
-   (xsource - xmin, 0, map)
+   map
+}
+
+impl Map
+{
+   /// returns the material at the given position, None outside of the map
+   fn get(&self, x: usize, y: usize) -> Option<Material>
+   {
+      let x = x.checked_sub(self.xmin)?;
+      self.cells.get(y).and_then(|row| row.get(x)).cloned()
+   }
+
+   fn set(&mut self, x: usize, y: usize, material: Material)
+   {
+      self.cells[y][x - self.xmin] = material;
+   }
+
+   /// iterates on the rows that are counted
+   fn counted_rows(&self) -> impl Iterator<Item = &Vec<Material>>
+   {
+      self.cells[self.ymin..=self.ymax].iter()
+   }
 }
 
 /// takes a material and outputs the corresponding char
@@ -103,8 +148,8 @@ fn char_of_material(m: Material) -> char
 /// displays the current map
 fn display(map: &Map)
 {
-   println!("");
-   for row in map
+   println!();
+   for row in &map.cells
    {
       let line: String = row.iter().map(|&m| char_of_material(m)).collect();
       println!("{}", line);
@@ -117,150 +162,350 @@ fn display(map: &Map)
 /// can we work on that square
 fn is_free(x: usize, y: usize, map: &Map) -> bool
 {
-   if (y >= map.len()) || (x >= map[0].len())
-   {
-      false
-   }
-   else
+   match map.get(x, y)
    {
-      let material = map[y][x];
-      material == Material::Sand || material == Material::Source
+      Some(material) => material == Material::Sand || material == Material::Source,
+      None => false
    }
 }
 
 /// can we go throu that material
 fn is_solid(x: usize, y: usize, map: &Map) -> bool
 {
-   if (y >= map.len()) || (x >= map[0].len())
-   {
-      false
-   }
-   else
+   match map.get(x, y)
    {
-      let material = map[y][x];
-      material == Material::Clay || material == Material::Water
+      Some(material) => material == Material::Clay || material == Material::Water,
+      None => false
    }
 }
 
 /// does the cell contains flowing water ?
 fn is_flowing(x: usize, y: usize, map: &Map) -> bool
 {
-   map[y][x] == Material::FlowingWater
+   map.get(x, y) == Some(Material::FlowingWater)
 }
 
-/// if we are in a container, fills the container with water
-fn fill_container(x: usize, y: usize, map: &mut Map)
+/// if we are in a container, fills the container with water and returns the filled columns
+fn fill_container(x: usize, y: usize, map: &mut Map) -> Option<(usize, usize)>
 {
-   /// was is the index of the last flowing cell before any solid cell
-   fn container_max(x: usize, y: usize, map: &Map) -> Option<usize>
+   // index of the last flowing cell before any solid cell
+   let mut xmax = Some(x);
+   while let Some(x) = xmax
    {
-      if is_flowing(x, y, &map)
+      if !is_flowing(x, y, map)
       {
-         if is_solid(x + 1, y, &map)
-         {
-            Some(x)
-         }
-         else
-         {
-            container_max(x + 1, y, &map)
-         }
+         xmax = None;
+      }
+      else if is_solid(x + 1, y, map)
+      {
+         break;
       }
       else
       {
-         None
+         xmax = Some(x + 1);
       }
    }
-   let xmax = container_max(x, y, &map);
 
-   /// was is the index of the first flowing cell, after a solid cell
-   fn container_min(x: usize, y: usize, map: &Map) -> Option<usize>
+   // index of the first flowing cell, after a solid cell
+   let mut xmin = Some(x);
+   while let Some(x) = xmin
    {
-      if is_flowing(x, y, &map)
+      if !is_flowing(x, y, map)
       {
-         if is_solid(x - 1, y, &map)
-         {
-            Some(x)
-         }
-         else
-         {
-            container_min(x - 1, y, &map)
-         }
+         xmin = None;
+      }
+      else if is_solid(x - 1, y, map)
+      {
+         break;
       }
       else
       {
-         None
+         xmin = Some(x - 1);
       }
    }
-   let xmin = container_min(x, y, &map);
 
    // if we are in a container, fills the container with water
    if let (Some(xmin), Some(xmax)) = (xmin, xmax)
    {
       for x in xmin..=xmax
       {
-         map[y][x] = Material::Water;
+         map.set(x, y, Material::Water);
       }
+      Some((xmin, xmax))
+   }
+   else
+   {
+      None
    }
 }
 
+/// where the simulation of a cell should resume once the cells it waits for have been simulated
+#[derive(Clone, Copy)]
+enum Stage
+{
+   Start,
+   AfterFall,
+   AfterRight,
+   AfterLeft
+}
+
 /// simulates the flow of water from our position
-fn simulate(x: usize, y: usize, mut map: &mut Map)
+/// uses an explicit stack (in place of recursion) so that deep maps do not overflow the call stack
+/// `on_update` is called each time the map changes
+fn simulate<F>(x: usize, y: usize, map: &mut Map, on_update: &mut F)
+   where F: FnMut(&Map)
 {
-   // is the current square workable
-   if is_free(x, y, &map)
+   let mut stack = vec![(x, y, Stage::Start)];
+   while let Some((x, y, stage)) = stack.pop()
    {
-      // put falling water in our current position
-      map[y][x] = Material::FallingWater;
-      //display(&map);
-
-      // waters flows under us if possible
-      simulate(x, y + 1, &mut map);
+      match stage
+      {
+         // is the current square workable
+         Stage::Start if is_free(x, y, map) =>
+         {
+            // put falling water in our current position
+            map.set(x, y, Material::FallingWater);
+            on_update(map);
+            // waters flows under us if possible
+            stack.push((x, y, Stage::AfterFall));
+            stack.push((x, y + 1, Stage::Start));
+         }
+         Stage::Start => (),
+         // are we on solid ground ?
+         Stage::AfterFall if is_solid(x, y + 1, map) =>
+         {
+            map.set(x, y, Material::FlowingWater);
+            on_update(map);
+            // water flows on the side
+            stack.push((x, y, Stage::AfterRight));
+            stack.push((x + 1, y, Stage::Start));
+         }
+         Stage::AfterFall => (),
+         Stage::AfterRight =>
+         {
+            stack.push((x, y, Stage::AfterLeft));
+            stack.push((x - 1, y, Stage::Start));
+         }
+         // fill our current container (if is is a container)
+         Stage::AfterLeft =>
+         {
+            if fill_container(x, y, map).is_some()
+            {
+               on_update(map);
+            }
+         }
+      }
+   }
+}
 
-      // are we on solid ground ?
-      if is_solid(x, y + 1, &map)
+/// simulates the springs one after the other and reports what each of them added to the map
+/// water stops on the water left by the previous springs, whose flow has already been fully simulated
+fn simulate_springs<F>(springs: &[(usize, usize)], map: &mut Map, mut on_update: F) -> Vec<Reach>
+   where F: FnMut(&Map)
+{
+   let reaches = springs.iter()
+                        .map(|&(x, y)| {
+                           let before = map.cells.clone();
+                           simulate(x, y, map, &mut on_update);
+
+                           let mut reach = Reach { x, y, nb_wet: 0, nb_settled: 0, ydeepest: None };
+                           let rows = before.iter().zip(map.cells.iter()).enumerate();
+                           for (y, (old_row, new_row)) in rows.take(map.ymax + 1).skip(map.ymin)
+                           {
+                              for (&old, &new) in old_row.iter().zip(new_row.iter())
+                              {
+                                 if old != new
+                                 {
+                                    reach.ydeepest = Some(y);
+                                    if old == Material::Sand || old == Material::Source
+                                    {
+                                       reach.nb_wet += 1;
+                                    }
+                                    if new == Material::Water
+                                    {
+                                       reach.nb_settled += 1;
+                                    }
+                                 }
+                              }
+                           }
+                           reach
+                        })
+                        .collect();
+
+   for &(x, y) in springs
+   {
+      if map.get(x, y).is_some()
       {
-         map[y][x] = Material::FlowingWater;
+         map.set(x, y, Material::Source);
+      }
+   }
+   on_update(map);
 
-         // water flows on the side
-         simulate(x + 1, y, &mut map);
-         simulate(x - 1, y, &mut map);
+   reaches
+}
 
-         // fill our current container (if is is a container)
-         fill_container(x, y, &mut map);
-      }
+/// displays the contribution of each spring
+fn display_reaches(reaches: &[Reach])
+{
+   for reach in reaches
+   {
+      let deepest = reach.ydeepest.map_or("nowhere".to_string(), |y| format!("y={}", y));
+      println!("spring at x={}, y={} : {} new wet squares, {} new stable squares, reaches {}",
+               reach.x, reach.y, reach.nb_wet, reach.nb_settled, deepest);
+   }
+}
+
+/// runs the tick by tick flow and displays when the rows of water filled up
+fn display_flow(spring: (usize, usize), intervals: &[Interval])
+{
+   let mut flow = Flow::new(&[spring], fill_map(&[spring], intervals));
+   flow.run(|_| ());
+
+   println!("water settled after {} ticks, {} rows filled", flow.tick, flow.fills.len());
+   if let (Some(first), Some(last)) = (flow.fills.first(), flow.fills.last())
+   {
+      println!("first row filled at tick {} (y={}, x={}..{}), last at tick {} (y={}, x={}..{})",
+               first.tick, first.y, first.xmin, first.xmax, last.tick, last.y, last.xmin, last.xmax);
    }
 }
 
 //-----------------------------------------------------------------------------
 // MAIN
 
-/// counts the number of wet squares
+/// returns the argument that follows the given flag on the command line
+fn flag_value(flag: &str) -> Option<String>
+{
+   let args: Vec<String> = std::env::args().collect();
+   args.windows(2).find(|pair| pair[0] == flag).map(|pair| pair[1].clone())
+}
+
+/// counts the number of wet squares between the first and last clay rows
 fn evaluate(map: &Map) -> usize
 {
-   map.iter().flat_map(|row| row).filter(|&&mat| mat != Material::Sand && mat != Material::Clay).count()
+   map.counted_rows().flatten().filter(|&&mat| mat != Material::Sand && mat != Material::Clay).count()
 }
 
-/// counts the number of square with stable water
+/// counts the number of square with stable water between the first and last clay rows
 fn evaluate_stable_water(map: &Map) -> usize
 {
-   map.iter().flat_map(|row| row).filter(|&&mat| mat == Material::Water).count()
+   map.counted_rows().flatten().filter(|&&mat| mat == Material::Water).count()
 }
 
 fn main()
 {
-   let (xsource, ysource) = (500, 0);
-   let input_path = "./data/test.txt";
+   let springs = [(500, 0)];
+   let input_path = "./data/input.txt";
    let intervals = input_data(input_path);
-   let (xsource, ysource, mut map) = fill_map(xsource, ysource, &intervals);
+   let mut map = fill_map(&springs, &intervals);
 
    // task1
-   simulate(xsource, ysource, &mut map);
-   map[ysource][xsource] = Material::Source;
-   display(&map);
+   let reaches = simulate_springs(&springs, &mut map, |_| ());
+   display_reaches(&reaches);
    let nb_water = evaluate(&map);
    println!("number of wet squares : {}", nb_water);
 
    // task2
    let nb_water_left = evaluate_stable_water(&map);
    println!("number of stable squares : {}", nb_water_left);
+
+   // images of the whole map and of its top, on request with `--images DIRECTORY`
+   let images_directory = flag_value("--images");
+   if let Some(directory) = &images_directory
+   {
+      std::fs::create_dir_all(directory).expect("Failed to create the image directory.");
+      let image_path = format!("{}/map.ppm", directory);
+      write_ppm(&image_path, &map, 1, None);
+      let top = Crop { xmin: 450, xmax: 550, ymin: 0, ymax: 100 };
+      let top_path = format!("{}/top.ppm", directory);
+      write_ppm(&top_path, &map, 4, Some(&top));
+      println!("Map written to {} and {}", image_path, top_path);
+   }
+
+   // tick by tick flow
+   display_flow(springs[0], &intervals);
+
+   // several springs
+   let springs = [(500, 0), (505, 0), (497, 8)];
+   let intervals = input_data("./data/test.txt");
+   let mut map = fill_map(&springs, &intervals);
+   let mut frames =
+      images_directory.map(|directory| FrameWriter::new(&format!("{}/frames", directory), 8, None, 1));
+   let reaches = simulate_springs(&springs, &mut map, |map| {
+                    if let Some(frames) = frames.as_mut()
+                    {
+                       frames.observe(map);
+                    }
+                 });
+   if let Some(frames) = &frames
+   {
+      println!("{} animation frames written to {}", frames.nb_frames(), frames.directory());
+   }
+   display(&map);
+   display_reaches(&reaches);
+   println!("number of wet squares : {}", evaluate(&map));
+}
+
+//-----------------------------------------------------------------------------
+// TESTS
+
+#[cfg(test)]
+mod tests
+{
+   use super::*;
+
+   /// runs the tick by tick flow and checks that it settles on the map computed by `simulate`
+   fn check_flow(spring: (usize, usize), intervals: &[Interval])
+   {
+      let mut map = fill_map(&[spring], intervals);
+      let mut flow = Flow::new(&[spring], map.clone());
+      simulate_springs(&[spring], &mut map, |_| ());
+      flow.run(|_| ());
+      assert!(flow.map.cells == map.cells, "The tick by tick flow does not settle on the static map!");
+   }
+
+   /// simulates the springs on the test map and returns their reaches and the total wet and stable squares
+   fn test_reaches(springs: &[(usize, usize)]) -> (Vec<Reach>, usize, usize)
+   {
+      let mut map = fill_map(springs, &input_data("./data/test.txt"));
+      let reaches = simulate_springs(springs, &mut map, |_| ());
+      (reaches, evaluate(&map), evaluate_stable_water(&map))
+   }
+
+   #[test]
+   fn single_spring_on_test()
+   {
+      let (reaches, nb_wet, nb_settled) = test_reaches(&[(500, 0)]);
+      assert_eq!((nb_wet, nb_settled), (57, 29));
+      assert_eq!((reaches[0].nb_wet, reaches[0].nb_settled, reaches[0].ydeepest), (57, 29, Some(13)));
+   }
+
+   #[test]
+   fn overlapping_springs_on_test()
+   {
+      // the second spring falls on the water overflowing from the top container and stops there
+      let (reaches, nb_wet, nb_settled) = test_reaches(&[(500, 0), (502, 0)]);
+      assert_eq!((reaches[0].nb_wet, reaches[0].nb_settled, reaches[0].ydeepest), (57, 29, Some(13)));
+      assert_eq!((reaches[1].nb_wet, reaches[1].nb_settled, reaches[1].ydeepest), (1, 0, Some(1)));
+      assert_eq!((nb_wet, nb_settled), (57 + 1, 29));
+   }
+
+   #[test]
+   fn duplicate_spring_on_test()
+   {
+      let (reaches, nb_wet, nb_settled) = test_reaches(&[(500, 0), (500, 0)]);
+      assert_eq!((reaches[1].nb_wet, reaches[1].nb_settled, reaches[1].ydeepest), (0, 0, None));
+      assert_eq!((nb_wet, nb_settled), (57, 29));
+   }
+
+   #[test]
+   fn flow_settles_on_test()
+   {
+      check_flow((500, 0), &input_data("./data/test.txt"));
+   }
+
+   #[test]
+   fn flow_settles_on_input()
+   {
+      check_flow((500, 0), &input_data("./data/input.txt"));
+   }
 }
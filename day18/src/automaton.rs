@@ -0,0 +1,485 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+#[cfg(test)]
+use std::fmt;
+use std::hash::{Hash, Hasher};
+
+//-----------------------------------------------------------------------------
+// TYPE
+
+/// a state that can be stored in a cell
+pub trait State: Copy + PartialEq + 'static
+{
+   /// all the states, `STATES[s.index()] == s`
+   const STATES: &'static [Self];
+
+   /// position of the state in `STATES`
+   fn index(self) -> usize;
+
+   /// character used to parse and display the state
+   fn to_char(self) -> char;
+
+   fn from_char(c: char) -> Option<Self>
+   {
+      Self::STATES.iter().cloned().find(|s| s.to_char() == c)
+   }
+}
+
+/// the cells that are counted around a cell
+#[derive(Clone, Copy)]
+pub enum Neighbourhood
+{
+   /// cells within the given chebyshev distance
+   Moore(usize),
+   /// cells within the given manhattan distance
+   #[cfg_attr(not(test), allow(dead_code))]
+   VonNeumann(usize)
+}
+
+/// what is found beyond the border of the grid
+#[derive(Clone, Copy)]
+pub enum Edge<S>
+{
+   /// nothing, cells outside the grid are not counted
+   Bounded,
+   /// the grid wraps around
+   #[cfg_attr(not(test), allow(dead_code))]
+   Toroidal,
+   /// cells outside the grid are in the given state
+   #[cfg_attr(not(test), allow(dead_code))]
+   Fixed(S)
+}
+
+/// a grid of cells with a second buffer to write the next generation
+#[derive(Clone)]
+pub struct Grid<S>
+{
+   pub width: usize,
+   pub height: usize,
+   cells: Vec<S>,
+   buffer: Vec<S>
+}
+
+/// the generations of a grid that ends up cycling
+/// generation n is found at `transient + (n - transient) % period` for n past the transient
+pub struct Cycle<S>
+{
+   pub transient: usize,
+   pub period: usize,
+   /// number of hash collisions between different grids met during the search
+   pub collisions: usize,
+   history: Vec<Grid<S>>
+}
+
+/// the rules of an automaton, tabulated by state and number of neighbours in each state
+pub struct Automaton<S>
+{
+   neighbourhood: Neighbourhood,
+   offsets: Vec<(isize, isize)>,
+   edge: Edge<S>,
+   table: Vec<S>
+}
+
+//-----------------------------------------------------------------------------
+// GRID
+
+impl<S: State> Grid<S>
+{
+   pub fn new(rows: Vec<Vec<S>>) -> Grid<S>
+   {
+      let height = rows.len();
+      let width = rows.first().map_or(0, |row| row.len());
+      if rows.iter().any(|row| row.len() != width)
+      {
+         panic!("All rows should have the same length!");
+      }
+      let cells: Vec<S> = rows.into_iter().flatten().collect();
+      let buffer = cells.clone();
+      Grid { width, height, cells, buffer }
+   }
+
+   #[cfg(test)]
+   pub fn parse(text: &str) -> Grid<S>
+   {
+      let rows = text.lines()
+                     .map(|line| line.trim())
+                     .filter(|line| !line.is_empty())
+                     .map(|line| line.chars().map(|c| S::from_char(c).expect("Unknown state!")).collect())
+                     .collect();
+      Grid::new(rows)
+   }
+
+   #[cfg(test)]
+   pub fn get(&self, row: usize, col: usize) -> S
+   {
+      self.cells[row * self.width + col]
+   }
+
+   #[cfg(test)]
+   pub fn set(&mut self, row: usize, col: usize, state: S)
+   {
+      self.cells[row * self.width + col] = state;
+   }
+
+   /// all the cells in reading order
+   pub fn cells(&self) -> &[S]
+   {
+      &self.cells
+   }
+
+   /// number of cells in each state
+   pub fn census(&self) -> Vec<usize>
+   {
+      let mut counts = vec![0; S::STATES.len()];
+      for cell in &self.cells
+      {
+         counts[cell.index()] += 1;
+      }
+      counts
+   }
+}
+
+/// the buffer is scratch space and is ignored when comparing grids
+impl<S: State> PartialEq for Grid<S>
+{
+   fn eq(&self, other: &Grid<S>) -> bool
+   {
+      self.width == other.width && self.height == other.height && self.cells == other.cells
+   }
+}
+
+impl<S: State> Hash for Grid<S>
+{
+   fn hash<H: Hasher>(&self, state: &mut H)
+   {
+      self.width.hash(state);
+      self.height.hash(state);
+      for cell in &self.cells
+      {
+         cell.index().hash(state);
+      }
+   }
+}
+
+#[cfg(test)]
+impl<S: State> fmt::Display for Grid<S>
+{
+   fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+   {
+      for row in self.cells.chunks(self.width.max(1))
+      {
+         let line: String = row.iter().map(|s| s.to_char()).collect();
+         writeln!(f, "{}", line)?;
+      }
+      Ok(())
+   }
+}
+
+//-----------------------------------------------------------------------------
+// RULES
+
+/// a comparison between a number of neighbours and a constant
+#[derive(Clone, Copy)]
+enum Comparison
+{
+   Equal,
+   Different,
+   Less,
+   LessOrEqual,
+   Greater,
+   GreaterOrEqual
+}
+
+/// `from -> to : state op value, ...`
+struct Rule
+{
+   from: usize,
+   to: usize,
+   conditions: Vec<(usize, Comparison, usize)>
+}
+
+impl Comparison
+{
+   fn parse(text: &str) -> Comparison
+   {
+      match text
+      {
+         "==" => Comparison::Equal,
+         "!=" => Comparison::Different,
+         "<" => Comparison::Less,
+         "<=" => Comparison::LessOrEqual,
+         ">" => Comparison::Greater,
+         ">=" => Comparison::GreaterOrEqual,
+         _ => panic!("Unknown comparison '{}'!", text)
+      }
+   }
+
+   fn holds(self, x: usize, y: usize) -> bool
+   {
+      match self
+      {
+         Comparison::Equal => x == y,
+         Comparison::Different => x != y,
+         Comparison::Less => x < y,
+         Comparison::LessOrEqual => x <= y,
+         Comparison::Greater => x > y,
+         Comparison::GreaterOrEqual => x >= y
+      }
+   }
+}
+
+/// parses a single state character
+fn parse_state<S: State>(text: &str) -> usize
+{
+   let mut chars = text.trim().chars();
+   match (chars.next(), chars.next())
+   {
+      (Some(c), None) => S::from_char(c).unwrap_or_else(|| panic!("Unknown state '{}'!", c)).index(),
+      _ => panic!("A state should be a single character, not '{}'!", text)
+   }
+}
+
+/// parses a rule of the form `from -> to : state op value, state op value`
+/// `from -> to` alone is an unconditional transition
+fn parse_rule<S: State>(text: &str) -> Rule
+{
+   let (transition, conditions) = match text.find(':')
+   {
+      Some(i) => (&text[..i], &text[i + 1..]),
+      None => (text, "")
+   };
+
+   let arrow = transition.find("->").expect("A rule should contain '->'!");
+   let from = parse_state::<S>(&transition[..arrow]);
+   let to = parse_state::<S>(&transition[arrow + 2..]);
+
+   let conditions = conditions.split(',')
+                              .filter(|condition| !condition.trim().is_empty())
+                              .map(|condition| {
+                                 let words: Vec<&str> = condition.split_whitespace().collect();
+                                 if words.len() != 3
+                                 {
+                                    panic!("A condition should read 'state op value', not '{}'!", condition);
+                                 }
+                                 let value =
+                                    words[2].parse().expect("Failed to parse a number of neighbours.");
+                                 (parse_state::<S>(words[0]), Comparison::parse(words[1]), value)
+                              })
+                              .collect();
+
+   Rule { from, to, conditions }
+}
+
+//-----------------------------------------------------------------------------
+// AUTOMATON
+
+/// the relative positions of the neighbours of a cell
+fn neighbour_offsets(neighbourhood: Neighbourhood) -> Vec<(isize, isize)>
+{
+   let (radius, within): (usize, fn(isize, isize, isize) -> bool) = match neighbourhood
+   {
+      Neighbourhood::Moore(radius) => (radius, |di, dj, r| di.abs().max(dj.abs()) <= r),
+      Neighbourhood::VonNeumann(radius) => (radius, |di, dj, r| di.abs() + dj.abs() <= r)
+   };
+
+   let r = radius as isize;
+   let mut offsets = Vec::new();
+   for di in -r..=r
+   {
+      for dj in -r..=r
+      {
+         if (di != 0 || dj != 0) && within(di, dj, r)
+         {
+            offsets.push((di, dj));
+         }
+      }
+   }
+   offsets
+}
+
+impl<S: State> Automaton<S>
+{
+   /// tabulates `transition(state, counts)` where `counts[s]` is the number of neighbours in state `s`
+   pub fn new<F>(neighbourhood: Neighbourhood, edge: Edge<S>, transition: F) -> Automaton<S>
+      where F: Fn(S, &[usize]) -> S
+   {
+      let offsets = neighbour_offsets(neighbourhood);
+      let nb_states = S::STATES.len();
+      let base = offsets.len() + 1;
+      let size = (0..nb_states).try_fold(nb_states, |size, _| size.checked_mul(base))
+                               .filter(|&size| size <= 1 << 24)
+                               .expect("Too many states or neighbours to tabulate the rules!");
+
+      let mut counts = vec![0; nb_states];
+      let table = (0..size).map(|mut key| {
+                              for count in counts.iter_mut().rev()
+                              {
+                                 *count = key % base;
+                                 key /= base;
+                              }
+                              transition(S::STATES[key], &counts)
+                           })
+                           .collect();
+
+      Automaton { neighbourhood, offsets, edge, table }
+   }
+
+   /// builds an automaton from rules separated by newlines or ';'
+   /// the first rule whose origin and conditions match gives the new state, the cell is unchanged otherwise
+   ///
+   /// `. -> | : | >= 3` turns open ground with at least three trees around into a tree
+   pub fn from_rules(neighbourhood: Neighbourhood, edge: Edge<S>, rules: &str) -> Automaton<S>
+   {
+      let rules: Vec<Rule> = rules.split(['\n', ';'])
+                                  .filter(|rule| !rule.trim().is_empty())
+                                  .map(parse_rule::<S>)
+                                  .collect();
+
+      Automaton::new(neighbourhood, edge, |state, counts| {
+                       rules.iter()
+                            .find(|rule| {
+                               rule.from == state.index()
+                               && rule.conditions
+                                      .iter()
+                                      .all(|&(s, comparison, value)| comparison.holds(counts[s], value))
+                            })
+                            .map_or(state, |rule| S::STATES[rule.to])
+                    })
+   }
+
+   /// returns the neighbour of a cell, if any
+   fn neighbour(&self, grid: &Grid<S>, row: usize, col: usize, (di, dj): (isize, isize)) -> Option<S>
+   {
+      let i = row as isize + di;
+      let j = col as isize + dj;
+      let (height, width) = (grid.height as isize, grid.width as isize);
+      if i >= 0 && i < height && j >= 0 && j < width
+      {
+         return Some(grid.cells[(i * width + j) as usize]);
+      }
+
+      match self.edge
+      {
+         Edge::Bounded => None,
+         Edge::Fixed(state) => Some(state),
+         Edge::Toroidal => Some(grid.cells[(i.rem_euclid(height) * width + j.rem_euclid(width)) as usize])
+      }
+   }
+
+   /// computes the next generation of the grid in place
+   pub fn step(&self, grid: &mut Grid<S>)
+   {
+      let base = self.offsets.len() + 1;
+      let mut counts = vec![0; S::STATES.len()];
+
+      for row in 0..grid.height
+      {
+         for col in 0..grid.width
+         {
+            counts.iter_mut().for_each(|count| *count = 0);
+            for &offset in &self.offsets
+            {
+               if let Some(state) = self.neighbour(grid, row, col, offset)
+               {
+                  counts[state.index()] += 1;
+               }
+            }
+
+            let state = grid.cells[row * grid.width + col];
+            let key = counts.iter().fold(state.index(), |key, &count| key * base + count);
+            grid.buffer[row * grid.width + col] = self.table[key];
+         }
+      }
+
+      std::mem::swap(&mut grid.cells, &mut grid.buffer);
+   }
+
+   /// computes n generations in place
+   pub fn run(&self, grid: &mut Grid<S>, n: usize)
+   {
+      for _ in 0..n
+      {
+         self.step(grid);
+      }
+   }
+
+   pub fn neighbourhood(&self) -> Neighbourhood
+   {
+      self.neighbourhood
+   }
+
+   pub fn edge(&self) -> Edge<S>
+   {
+      self.edge
+   }
+
+   /// the new states indexed by `state * base^k + counts[0] * base^(k-1) + ... + counts[k-1]`
+   /// where k is the number of states and base is the number of neighbours plus one
+   pub fn table(&self) -> &[S]
+   {
+      &self.table
+   }
+}
+
+//-----------------------------------------------------------------------------
+// CYCLE
+
+/// returns the hash of a full grid
+fn fingerprint<S: State>(grid: &Grid<S>) -> u64
+{
+   let mut hasher = DefaultHasher::new();
+   grid.hash(&mut hasher);
+   hasher.finish()
+}
+
+impl<S: State> Automaton<S>
+{
+   /// runs the automaton until a grid comes back, hashing every generation
+   /// grids with the same hash are compared cell by cell so a collision cannot produce a wrong period
+   pub fn find_cycle(&self, grid: &Grid<S>, max_generations: usize) -> Option<Cycle<S>>
+   {
+      let mut history = vec![grid.clone()];
+      let mut generations_of_hash: HashMap<u64, Vec<usize>> = HashMap::new();
+      generations_of_hash.insert(fingerprint(grid), vec![0]);
+      let mut collisions = 0;
+      let mut grid = grid.clone();
+
+      for generation in 1..=max_generations
+      {
+         self.step(&mut grid);
+         let generations = generations_of_hash.entry(fingerprint(&grid)).or_default();
+         match generations.iter().find(|&&previous| history[previous] == grid)
+         {
+            Some(&transient) =>
+            {
+               let period = generation - transient;
+               return Some(Cycle { transient, period, collisions, history });
+            }
+            None =>
+            {
+               collisions += generations.len();
+               generations.push(generation);
+               history.push(grid.clone());
+            }
+         }
+      }
+
+      None
+   }
+}
+
+impl<S: State> Cycle<S>
+{
+   /// returns the grid after n generations without simulating them
+   pub fn at(&self, n: usize) -> &Grid<S>
+   {
+      if n < self.transient
+      {
+         &self.history[n]
+      }
+      else
+      {
+         &self.history[self.transient + (n - self.transient) % self.period]
+      }
+   }
+}
@@ -1,6 +1,10 @@
-use std::collections::HashMap;
+mod automaton;
+mod packed;
+use automaton::{Automaton, Edge, Grid, Neighbourhood, State};
+use packed::{PackedAutomaton, PackedGrid};
 use std::fs::File;
 use std::io::{BufRead, BufReader};
+use std::time::Instant;
 
 //-----------------------------------------------------------------------------
 // TYPE
@@ -14,15 +18,27 @@ enum Acre
    Lumberyard
 }
 
-/// describes the neibourhood of an acre
-struct Neigbourhood
+impl State for Acre
 {
-   nb_tree: usize,
-   nb_ground: usize,
-   nb_lumberyard: usize
+   const STATES: &'static [Acre] = &[Acre::Tree, Acre::Ground, Acre::Lumberyard];
+
+   fn index(self) -> usize
+   {
+      self as usize
+   }
+
+   fn to_char(self) -> char
+   {
+      match self
+      {
+         Acre::Tree => '|',
+         Acre::Ground => '.',
+         Acre::Lumberyard => '#'
+      }
+   }
 }
 
-type Lumber = [Vec<Acre>];
+type Lumber = Grid<Acre>;
 
 //-----------------------------------------------------------------------------
 // INPUT
@@ -43,135 +59,209 @@ fn parse_line(line: &str) -> Vec<Acre>
    line.chars().map(parse_char).collect()
 }
 
-fn input_data(path: &str) -> Vec<Vec<Acre>>
+fn input_data(path: &str) -> Lumber
 {
    let file = File::open(path).expect("Failed to open input file.");
-   BufReader::new(file).lines()
-                       .map(|line_option| line_option.expect("Failed to load a line."))
-                       .map(|line| parse_line(&line))
-                       .collect()
+   let rows = BufReader::new(file).lines()
+                                  .map(|line_option| line_option.expect("Failed to load a line."))
+                                  .map(|line| parse_line(&line))
+                                  .collect();
+   Grid::new(rows)
 }
 
 //-----------------------------------------------------------------------------
 // SIMULATE
 
-/// returns the neibourhood of a cell
-fn get_neibours(i0: usize, j0: usize, lumber: &Lumber) -> Neigbourhood
+/// the rules of the lumber collection area
+const LUMBER_RULES: &str = ". -> | : | >= 3
+                            | -> # : # >= 3
+                            # -> . : | == 0
+                            # -> . : # == 0";
+
+/// returns an automaton that simulates a lumber collection area
+fn lumber_automaton() -> Automaton<Acre>
 {
-   let mut neibourhood = Neigbourhood { nb_tree: 0, nb_ground: 0, nb_lumberyard: 0 };
+   Automaton::from_rules(Neighbourhood::Moore(1), Edge::Bounded, LUMBER_RULES)
+}
 
-   let mini = if i0 == 0 { 0 } else { i0 - 1 };
-   let maxi = std::cmp::min(i0 + 1, lumber.len() - 1);
-   let minj = if j0 == 0 { 0 } else { j0 - 1 };
-   let maxj = std::cmp::min(j0 + 1, lumber[0].len() - 1);
+/// simulate n minutes
+fn simulate(lumber: &Lumber, nb_minutes: usize) -> Lumber
+{
+   let mut lumber = lumber.clone();
+   lumber_automaton().run(&mut lumber, nb_minutes);
+   lumber
+}
 
-   for i in mini..=maxi
+/// finds the exact cycle of the lumber and jumps straight to minute n
+fn simulate_periodic(lumber: &Lumber, nb_minutes: usize) -> Lumber
+{
+   let automaton = lumber_automaton();
+   let max_minutes = 100_000;
+   let cycle = automaton.find_cycle(lumber, max_minutes).expect("Failed to find a cycle.");
+   println!("Found a period of {} after a transient of {} minutes ({} hash collisions).",
+            cycle.period, cycle.transient, cycle.collisions);
+   cycle.at(nb_minutes).clone()
+}
+
+//-----------------------------------------------------------------------------
+// STATISTICS
+
+/// the content of the lumber at a given minute
+struct Sample
+{
+   minute: usize,
+   nb_tree: usize,
+   nb_ground: usize,
+   nb_lumberyard: usize,
+   resource_value: usize
+}
+
+/// the evolution of the lumber minute by minute
+/// the cycle, if it was reached, starts at minute `transient` and repeats every `period` minutes
+struct TimeSeries
+{
+   samples: Vec<Sample>,
+   cycle: Option<(usize, usize)>
+}
+
+/// records the content of the lumber from minute 0 to minute n (included)
+fn record_series(lumber: &Lumber, nb_minutes: usize) -> TimeSeries
+{
+   let automaton = lumber_automaton();
+   let cycle = automaton.find_cycle(lumber, nb_minutes).map(|cycle| (cycle.transient, cycle.period));
+
+   let mut lumber = lumber.clone();
+   let mut samples = Vec::with_capacity(nb_minutes + 1);
+   for minute in 0..=nb_minutes
    {
-      for j in minj..=maxj
-      {
-         if (i != i0) || (j != j0)
-         {
-            match lumber[i][j]
-            {
-               Acre::Ground => neibourhood.nb_ground += 1,
-               Acre::Lumberyard => neibourhood.nb_lumberyard += 1,
-               Acre::Tree => neibourhood.nb_tree += 1
-            }
-         }
-      }
+      let counts = lumber.census();
+      let nb_tree = counts[Acre::Tree.index()];
+      let nb_lumberyard = counts[Acre::Lumberyard.index()];
+      samples.push(Sample { minute,
+                            nb_tree,
+                            nb_ground: counts[Acre::Ground.index()],
+                            nb_lumberyard,
+                            resource_value: nb_tree * nb_lumberyard });
+      automaton.step(&mut lumber);
    }
 
-   neibourhood
+   TimeSeries { samples, cycle }
 }
 
-/// returns the future state of a cell
-fn simulate_acre(i: usize, j: usize, lumber: &Lumber) -> Acre
+/// returns the number of full periods completed at a given minute, None during the transient
+fn cycle_number(series: &TimeSeries, minute: usize) -> Option<usize>
 {
-   let n = get_neibours(i, j, &lumber);
-   match lumber[i][j]
+   match series.cycle
    {
-      Acre::Ground if n.nb_tree >= 3 => Acre::Tree,
-      Acre::Tree if n.nb_lumberyard >= 3 => Acre::Lumberyard,
-      Acre::Lumberyard if n.nb_tree == 0 || n.nb_lumberyard == 0 => Acre::Ground,
-      acre => acre
+      Some((transient, period)) if minute >= transient => Some((minute - transient) / period),
+      _ => None
    }
 }
 
-/// returns the future state of a whole lumber
-fn simulate_one_minute(lumber: &Lumber) -> Vec<Vec<Acre>>
+/// one line per minute, the cycle column is empty during the transient
+fn series_to_csv(series: &TimeSeries) -> String
 {
-   (0..lumber.len()).map(|i| (0..lumber[0].len()).map(|j| simulate_acre(i, j, &lumber)).collect()).collect()
+   let mut csv = String::from("minute,trees,ground,lumberyards,resource_value,cycle\n");
+   for sample in &series.samples
+   {
+      let cycle = cycle_number(series, sample.minute).map_or(String::new(), |cycle| cycle.to_string());
+      csv += &format!("{},{},{},{},{},{}\n",
+                      sample.minute,
+                      sample.nb_tree,
+                      sample.nb_ground,
+                      sample.nb_lumberyard,
+                      sample.resource_value,
+                      cycle);
+   }
+   csv
 }
 
-/// simulate n minutes
-fn simulate(lumber: &Lumber, nb_minutes: usize) -> Vec<Vec<Acre>>
+fn series_to_json(series: &TimeSeries) -> String
 {
-   let mut lumber = lumber.to_vec();
+   let samples: Vec<String> =
+      series.samples
+            .iter()
+            .map(|sample| {
+               format!(concat!("{{\"minute\":{},\"trees\":{},\"ground\":{},",
+                               "\"lumberyards\":{},\"resource_value\":{}}}"),
+                       sample.minute,
+                       sample.nb_tree,
+                       sample.nb_ground,
+                       sample.nb_lumberyard,
+                       sample.resource_value)
+            })
+            .collect();
 
-   for _minute in 0..nb_minutes
+   let cycle = match series.cycle
    {
-      lumber = simulate_one_minute(&lumber);
-   }
+      Some((transient, period)) => format!("{{\"transient\":{},\"period\":{}}}", transient, period),
+      None => "null".to_string()
+   };
 
-   lumber
+   format!("{{\"cycle\":{},\"minutes\":[{}]}}", cycle, samples.join(","))
 }
 
-/// simulate n minutes but skip ahead once a period has been found in the scores
-fn simulate_periodic(lumber: &Lumber, nb_minutes: usize) -> Vec<Vec<Acre>>
+//-----------------------------------------------------------------------------
+// BENCHMARK
+
+/// returns a pseudo-random grid (xorshift)
+fn random_grid<S: State>(width: usize, height: usize, seed: u64) -> Grid<S>
 {
-   let mut lumber = lumber.to_vec();
-   let mut previous_scores = HashMap::new();
-   let mut potential_period = 0;
+   let mut random = seed.max(1);
+   let rows = (0..height).map(|_| {
+                            (0..width).map(|_| {
+                                         random ^= random << 13;
+                                         random ^= random >> 7;
+                                         random ^= random << 17;
+                                         S::STATES[(random % S::STATES.len() as u64) as usize]
+                                      })
+                                      .collect()
+                         })
+                         .collect();
+   Grid::new(rows)
+}
 
-   for minute in 0..nb_minutes
-   {
-      lumber = simulate_one_minute(&lumber);
+/// times the generic and the packed steppers on a large random lumber
+fn benchmark_steppers(size: usize, nb_minutes: usize)
+{
+   let automaton = lumber_automaton();
+   let lumber = random_grid::<Acre>(size, size, 1234);
 
-      let score = evaluate(&lumber);
-      if let Some(previous_minute) = previous_scores.get(&score)
-      {
-         // we found a period
-         let period = minute - previous_minute;
-         if period == potential_period
-         {
-            // the period has been confirmed
-            println!("Found a period of {}.", period);
-            let time_left = (nb_minutes - minute - 1) % period;
-            return simulate(&lumber, time_left);
-         }
-         else
-         {
-            potential_period = period;
-         }
-      }
-      previous_scores.insert(score, minute);
-   }
+   let start = Instant::now();
+   let mut generic = lumber.clone();
+   automaton.run(&mut generic, nb_minutes);
+   let generic_time = start.elapsed();
 
-   lumber
+   let mut packed = PackedGrid::from_grid(&lumber);
+   let packed_automaton = PackedAutomaton::new(&automaton);
+   let start = Instant::now();
+   packed_automaton.run(&mut packed, nb_minutes);
+   let packed_time = start.elapsed();
+
+   println!("{} minutes on a {}x{} lumber: generic {:?}, packed {:?} on {} threads",
+            nb_minutes,
+            size,
+            size,
+            generic_time,
+            packed_time,
+            rayon::current_num_threads());
+   println!("resource values: generic {}, packed {}", evaluate(&generic), evaluate(&packed.to_grid()));
 }
 
 //-----------------------------------------------------------------------------
 // MAIN
 
-fn evaluate(lumber: &Lumber) -> usize
+/// returns the argument that follows the given flag on the command line
+fn flag_value(flag: &str) -> Option<String>
 {
-   let mut nb_tree = 0;
-   let mut nb_lumberyard = 0;
-
-   for row in lumber
-   {
-      for acre in row
-      {
-         match acre
-         {
-            Acre::Lumberyard => nb_lumberyard += 1,
-            Acre::Tree => nb_tree += 1,
-            Acre::Ground => ()
-         }
-      }
-   }
+   let args: Vec<String> = std::env::args().collect();
+   args.windows(2).find(|pair| pair[0] == flag).map(|pair| pair[1].clone())
+}
 
-   nb_tree * nb_lumberyard
+fn evaluate(lumber: &Lumber) -> usize
+{
+   let counts = lumber.census();
+   counts[Acre::Tree.index()] * counts[Acre::Lumberyard.index()]
 }
 
 fn main()
@@ -182,12 +272,215 @@ fn main()
    // task1
    let nb_minutes = 10;
    let new_lumber = simulate(&lumber, nb_minutes);
-   let score = evaluate(&new_lumber);
-   println!("Score after {} minutes : {}", nb_minutes, score);
+   let score_task1 = evaluate(&new_lumber);
+   println!("Score after {} minutes : {}", nb_minutes, score_task1);
 
    // task2
    let nb_big_minutes = 1_000_000_000;
    let new_lumber = simulate_periodic(&lumber, nb_big_minutes);
    let score = evaluate(&new_lumber);
    println!("Score after {} minutes : {}", nb_big_minutes, score);
+
+   // exports the evolution of the lumber, on request, with `--csv PATH` and `--json PATH`
+   let csv_path = flag_value("--csv");
+   let json_path = flag_value("--json");
+   if csv_path.is_some() || json_path.is_some()
+   {
+      let series = record_series(&lumber, 600);
+      if let Some(csv_path) = csv_path
+      {
+         std::fs::write(&csv_path, series_to_csv(&series)).expect("Failed to write the csv file.");
+         println!("Time series written to {}", csv_path);
+      }
+      if let Some(json_path) = json_path
+      {
+         std::fs::write(&json_path, series_to_json(&series)).expect("Failed to write the json file.");
+         println!("Time series written to {}", json_path);
+      }
+   }
+
+   // timing of the packed stepper, on request
+   if std::env::args().any(|arg| arg == "--bench")
+   {
+      benchmark_steppers(2000, 20);
+   }
+}
+
+//-----------------------------------------------------------------------------
+// TESTS
+
+#[cfg(test)]
+mod tests
+{
+   use super::*;
+
+   /// checks that the packed stepper agrees with the generic one on a small random grid
+   fn check_packed<S: State>(name: &str, automaton: &Automaton<S>, width: usize, height: usize)
+   {
+      let mut grid = random_grid::<S>(width, height, 42);
+      let mut packed = PackedGrid::from_grid(&grid);
+      let packed_automaton = PackedAutomaton::new(automaton);
+      for minute in 0..20
+      {
+         automaton.step(&mut grid);
+         packed_automaton.step(&mut packed);
+         assert!(packed.to_grid::<S>() == grid, "{}: the packed stepper diverged at step {}!", name, minute);
+      }
+   }
+
+   #[test]
+   fn packed_matches_generic()
+   {
+      check_packed("lumber", &lumber_automaton(), 37, 23);
+      check_packed("large lumber", &lumber_automaton(), 300, 200);
+      check_packed("toroidal lumber",
+                   &Automaton::<Acre>::from_rules(Neighbourhood::Moore(1), Edge::Toroidal, LUMBER_RULES),
+                   37,
+                   23);
+      check_packed("fixed lumber",
+                   &Automaton::from_rules(Neighbourhood::Moore(2), Edge::Fixed(Acre::Tree), LUMBER_RULES),
+                   37,
+                   23);
+      check_packed("wide fixed lumber",
+                   &Automaton::from_rules(Neighbourhood::Moore(4), Edge::Fixed(Acre::Ground), LUMBER_RULES),
+                   70,
+                   101);
+      check_packed("tiny toroidal lumber",
+                   &Automaton::<Acre>::from_rules(Neighbourhood::Moore(3), Edge::Toroidal, LUMBER_RULES),
+                   5,
+                   4);
+   }
+
+   #[test]
+   fn packed_matches_task1()
+   {
+      let lumber = input_data("./data/input.txt");
+      let mut packed = PackedGrid::from_grid(&lumber);
+      PackedAutomaton::new(&lumber_automaton()).run(&mut packed, 10);
+      let expected = simulate(&lumber, 10);
+      assert!(packed.to_grid::<Acre>() == expected, "The packed stepper disagrees on the input!");
+   }
+
+   /// checks the jump along the cycle against a plain simulation
+   #[test]
+   fn periodic_matches_plain_simulation()
+   {
+      let lumber = input_data("./data/input.txt");
+      let cycle = lumber_automaton().find_cycle(&lumber, 100_000).expect("Failed to find a cycle.");
+      let last_minute = cycle.transient + 3 * cycle.period + 1;
+      let mut plain = lumber.clone();
+      for minute in 0..=last_minute
+      {
+         assert!(cycle.at(minute) == &plain, "The cycle is wrong at minute {}!", minute);
+         lumber_automaton().step(&mut plain);
+      }
+      let jump = simulate_periodic(&lumber, last_minute);
+      assert!(jump == simulate(&lumber, last_minute), "The jump is wrong!");
+   }
+
+   /// a cell of Conway's game of life
+   #[derive(Clone, Copy, PartialEq)]
+   enum Life
+   {
+      Dead,
+      Alive
+   }
+
+   impl State for Life
+   {
+      const STATES: &'static [Life] = &[Life::Dead, Life::Alive];
+
+      fn index(self) -> usize
+      {
+         self as usize
+      }
+
+      fn to_char(self) -> char
+      {
+         match self
+         {
+            Life::Dead => '.',
+            Life::Alive => '#'
+         }
+      }
+   }
+
+   /// runs a glider on a torus
+   /// it should come back to its starting position after 4 generations per cell of width
+   #[test]
+   fn glider_cycles_on_torus()
+   {
+      let automaton =
+         Automaton::new(Neighbourhood::Moore(1), Edge::Toroidal, |cell, counts| {
+            match (cell, counts[Life::Alive.index()])
+            {
+               (_, 3) | (Life::Alive, 2) => Life::Alive,
+               _ => Life::Dead
+            }
+         });
+      let glider = Grid::<Life>::parse(".#......
+                                        ..#.....
+                                        ###.....
+                                        ........
+                                        ........
+                                        ........
+                                        ........
+                                        ........");
+
+      let mut grid = glider.clone();
+      let nb_generations = 4 * grid.width;
+      automaton.run(&mut grid, nb_generations);
+      assert!(grid.cells() == glider.cells(), "The glider did not come back!\n{}", grid);
+      let cycle = automaton.find_cycle(&glider, 1000).expect("The glider should cycle.");
+      assert_eq!((cycle.transient, cycle.period), (0, nb_generations), "Wrong glider cycle!");
+
+      // the same game with the rule DSL
+      let dsl = Automaton::from_rules(Neighbourhood::Moore(1),
+                                      Edge::Toroidal,
+                                      "# -> . : # < 2; # -> . : # > 3; . -> # : # == 3");
+      let mut grid_dsl = glider.clone();
+      automaton.run(&mut grid, 7);
+      dsl.run(&mut grid_dsl, 7);
+      assert!(grid.cells() == grid_dsl.cells(), "The two games of life disagree!");
+   }
+
+   /// a single live cell grows into a diamond in a von Neumann neighbourhood
+   #[test]
+   fn cell_grows_into_diamond()
+   {
+      let growth =
+         Automaton::from_rules(Neighbourhood::VonNeumann(1), Edge::Fixed(Life::Dead), ". -> # : # >= 1");
+      let size = 21;
+      let mut grid = Grid::new(vec![vec![Life::Dead; size]; size]);
+      grid.set(size / 2, size / 2, Life::Alive);
+      let nb_generations = 5;
+      growth.run(&mut grid, nb_generations);
+      let nb_alive = grid.census()[Life::Alive.index()];
+      let diamond_size = 2 * nb_generations * nb_generations + 2 * nb_generations + 1;
+      assert_eq!(nb_alive, diamond_size, "Wrong diamond size!");
+      assert!(grid.get(size / 2, size / 2 + nb_generations) == Life::Alive, "The diamond is too small!");
+      assert!(grid.get(size / 2 + 1, size / 2 + nb_generations) == Life::Dead, "The diamond is too large!");
+   }
+
+   #[test]
+   fn series_matches_simulation()
+   {
+      let lumber = input_data("./data/input.txt");
+      let nb_minutes = 600;
+      let series = record_series(&lumber, nb_minutes);
+      for &minute in [0, 10, 467, 600].iter()
+      {
+         assert_eq!(series.samples[minute].resource_value, evaluate(&simulate(&lumber, minute)));
+      }
+      assert_eq!(series.cycle, Some((467, 28)));
+      assert_eq!(cycle_number(&series, 466), None);
+      assert_eq!(cycle_number(&series, 467 + 28), Some(1));
+
+      let csv = series_to_csv(&series);
+      assert_eq!(csv.lines().count(), nb_minutes + 2);
+      assert!(csv.ends_with(&format!(",{}\n", (nb_minutes - 467) / 28)));
+      let json = series_to_json(&series);
+      assert!(json.starts_with("{\"cycle\":{\"transient\":467,\"period\":28},\"minutes\":[{\"minute\":0,"));
+      assert_eq!(json.matches("\"minute\":").count(), nb_minutes + 1);
+   }
 }
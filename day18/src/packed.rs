@@ -0,0 +1,244 @@
+use crate::automaton::{Automaton, Edge, Grid, Neighbourhood, State};
+use rayon::prelude::*;
+
+//-----------------------------------------------------------------------------
+// TYPE
+
+/// a grid stored as one byte per cell (the index of its state) with a second buffer for the next generation
+#[derive(Clone)]
+pub struct PackedGrid
+{
+   pub width: usize,
+   pub height: usize,
+   cells: Vec<u8>,
+   buffer: Vec<u8>
+}
+
+/// number of consecutive rows that a thread computes while sliding its column sums
+const ROWS_PER_BAND: usize = 32;
+
+/// the rules of a Moore automaton in a form suited to stepping packed grids
+/// neighbours are counted with column sums sliding down the rows and windows sliding along each row
+/// so that a cell costs the same whatever the radius, bands of rows are split across threads
+pub struct PackedAutomaton
+{
+   radius: usize,
+   base: usize,
+   edge: Edge<u8>,
+   /// `weights[s]` is what one neighbour in state s adds to a key of the table
+   weights: Vec<u32>,
+   table: Vec<u8>
+}
+
+/// per thread buffers used to compute a band of rows
+struct Scratch
+{
+   /// sum of the weights of the cells in each (padded) column segment centered on the current row
+   /// the weights of the different states do not overlap so this holds the count of every state at once
+   column_sums: Vec<u32>
+}
+
+impl Scratch
+{
+   fn new(width: usize, radius: usize) -> Scratch
+   {
+      Scratch { column_sums: vec![0; width + 2 * radius] }
+   }
+}
+
+//-----------------------------------------------------------------------------
+// GRID
+
+impl PackedGrid
+{
+   pub fn from_grid<S: State>(grid: &Grid<S>) -> PackedGrid
+   {
+      if S::STATES.len() > 256
+      {
+         panic!("A packed grid holds at most 256 states!");
+      }
+      let cells: Vec<u8> = grid.cells().iter().map(|cell| cell.index() as u8).collect();
+      let buffer = cells.clone();
+      PackedGrid { width: grid.width, height: grid.height, cells, buffer }
+   }
+
+   pub fn to_grid<S: State>(&self) -> Grid<S>
+   {
+      let rows = self.cells
+                     .chunks(self.width.max(1))
+                     .map(|row| row.iter().map(|&cell| S::STATES[cell as usize]).collect())
+                     .collect();
+      Grid::new(rows)
+   }
+}
+
+//-----------------------------------------------------------------------------
+// AUTOMATON
+
+impl PackedAutomaton
+{
+   pub fn new<S: State>(automaton: &Automaton<S>) -> PackedAutomaton
+   {
+      let radius = match automaton.neighbourhood()
+      {
+         Neighbourhood::Moore(radius) => radius,
+         Neighbourhood::VonNeumann(_) => panic!("The packed stepper only handles Moore neighbourhoods!")
+      };
+      let edge = match automaton.edge()
+      {
+         Edge::Bounded => Edge::Bounded,
+         Edge::Toroidal => Edge::Toroidal,
+         Edge::Fixed(state) => Edge::Fixed(state.index() as u8)
+      };
+      let nb_states = S::STATES.len();
+      let base = (2 * radius + 1) * (2 * radius + 1);
+      let weights = (0..nb_states).map(|s| base.pow((nb_states - 1 - s) as u32) as u32).collect();
+      let table = automaton.table().iter().map(|state| state.index() as u8).collect();
+      PackedAutomaton { radius, base, edge, weights, table }
+   }
+
+   /// returns the row found at the given (potentially out of the grid) position, if any
+   fn row_index(&self, i: isize, height: usize) -> Option<usize>
+   {
+      match (i >= 0 && i < height as isize, self.edge)
+      {
+         (true, _) => Some(i as usize),
+         (false, Edge::Toroidal) => Some(i.rem_euclid(height as isize) as usize),
+         (false, _) => None
+      }
+   }
+
+   /// adds (or removes) the weights of a row of the grid to the running column sums
+   fn update_column_sums(&self,
+                         cells: &[u8],
+                         width: usize,
+                         i: Option<usize>,
+                         add: bool,
+                         scratch: &mut Scratch)
+   {
+      let i = match i
+      {
+         Some(i) => i,
+         None => return
+      };
+      let r = self.radius;
+      let line = &cells[i * width..(i + 1) * width];
+      let sums = scratch.column_sums[r..r + width].iter_mut().zip(line);
+      if add
+      {
+         sums.for_each(|(sum, &cell)| *sum += self.weights[cell as usize]);
+      }
+      else
+      {
+         sums.for_each(|(sum, &cell)| *sum -= self.weights[cell as usize]);
+      }
+   }
+
+   /// computes one row of the next generation from the column sums of the 2r+1 rows centered on it
+   fn step_row(&self,
+               cells: &[u8],
+               (width, height): (usize, usize),
+               row: usize,
+               output: &mut [u8],
+               scratch: &mut Scratch)
+   {
+      let r = self.radius;
+      let sums = &mut scratch.column_sums;
+
+      // columns are padded with r columns on each side (empty or wrapped around)
+      if let Edge::Toroidal = self.edge
+      {
+         for j in 0..r
+         {
+            sums[j] = sums[r + (j as isize - r as isize).rem_euclid(width as isize) as usize];
+            sums[r + width + j] = sums[r + j % width];
+         }
+      }
+
+      // cells outside the grid for a fixed edge
+      let nb_rows_inside = std::cmp::min(row + r, height - 1) + 1 - row.saturating_sub(r);
+      let outside_weight = match self.edge
+      {
+         Edge::Fixed(outside) => self.weights[outside as usize],
+         _ => 0
+      };
+
+      // window of 2r+1 columns sliding along the row, one column in and one column out
+      // the cell itself is removed from its window and its own state added in front of the key
+      let line = &cells[row * width..(row + 1) * width];
+      let cell_weight = self.weights[0] * self.base as u32;
+      let mut window: u32 = sums[..2 * r + 1].iter().sum();
+      for (j, (new_cell, &cell)) in output.iter_mut().zip(line).enumerate()
+      {
+         let mut key = cell as u32 * cell_weight + window - self.weights[cell as usize];
+         if outside_weight != 0
+         {
+            let nb_cols_inside = std::cmp::min(j + r, width - 1) + 1 - j.saturating_sub(r);
+            key += (self.base - nb_rows_inside * nb_cols_inside) as u32 * outside_weight;
+         }
+         *new_cell = self.table[key as usize];
+         if j + 1 < width
+         {
+            window = window + sums[j + 2 * r + 1] - sums[j];
+         }
+      }
+   }
+
+   /// computes a band of consecutive rows of the next generation
+   /// the column sums are built once for the first row then updated by one row in and one row out
+   fn step_band(&self,
+                cells: &[u8],
+                (width, height): (usize, usize),
+                first_row: usize,
+                output: &mut [u8],
+                scratch: &mut Scratch)
+   {
+      let r = self.radius as isize;
+      let first_row = first_row as isize;
+      scratch.column_sums.iter_mut().for_each(|sum| *sum = 0);
+      for i in (first_row - r)..=(first_row + r)
+      {
+         self.update_column_sums(cells, width, self.row_index(i, height), true, scratch);
+      }
+
+      for (k, output_row) in output.chunks_mut(width).enumerate()
+      {
+         let row = first_row + k as isize;
+         if k > 0
+         {
+            self.update_column_sums(cells, width, self.row_index(row - r - 1, height), false, scratch);
+            self.update_column_sums(cells, width, self.row_index(row + r, height), true, scratch);
+         }
+         self.step_row(cells, (width, height), row as usize, output_row, scratch);
+      }
+   }
+
+   /// computes the next generation of the grid in place
+   /// the grid is cut into bands of rows that are computed in parallel
+   pub fn step(&self, grid: &mut PackedGrid)
+   {
+      let (width, height) = (grid.width, grid.height);
+      if width == 0 || height == 0
+      {
+         return;
+      }
+      let cells = &grid.cells;
+      grid.buffer
+          .par_chunks_mut(width * ROWS_PER_BAND)
+          .enumerate()
+          .for_each_init(|| Scratch::new(width, self.radius),
+                         |scratch, (band, output)| {
+                            self.step_band(cells, (width, height), band * ROWS_PER_BAND, output, scratch)
+                         });
+      std::mem::swap(&mut grid.cells, &mut grid.buffer);
+   }
+
+   /// computes n generations in place
+   pub fn run(&self, grid: &mut PackedGrid, n: usize)
+   {
+      for _ in 0..n
+      {
+         self.step(grid);
+      }
+   }
+}
@@ -1,9 +1,17 @@
 #![feature(slice_patterns)]
-use std::collections::HashSet;
+#[cfg(test)]
+mod maze;
+mod regex;
+use regex::{Heading, Node, ParseError};
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::time::Instant;
 
 //-----------------------------------------------------------------------------
 // TYPES
 
+/// a direction, or a crossing between several routes
+/// it is dropped and compared with explicit stacks as routes can be nested arbitrarily deep
 enum Direction
 {
    North,
@@ -15,8 +23,16 @@ enum Direction
 
 type Route = Vec<Direction>;
 
+/// coordinates of a room, north increases the first coordinate and east the second
 type Position = (i32, i32);
 
+/// the rooms of the facility and, for each room, the rooms behind its doors
+#[derive(Default)]
+struct Facility
+{
+   adjacency: HashMap<Position, Vec<Position>>
+}
+
 #[derive(Clone, Copy, PartialEq)]
 enum Base
 {
@@ -29,131 +45,336 @@ enum Base
 type Map = [Vec<Base>];
 
 //-----------------------------------------------------------------------------
-// ROUTE PARSING
+// DIRECTION
 
-/// reads a string and returns a couple (route, non-consummed chars)
-fn make_route(mut regexp: &[char]) -> (Route, &[char])
+impl Direction
 {
-   let mut route = Vec::new();
-
-   while !regexp.is_empty()
+   /// the routes of a crossing, none for a single direction
+   fn routes(&self) -> &[Route]
    {
-      match regexp[0]
+      match self
       {
-         '^' | '$' => (),
-         '|' | ')' => return (route, regexp),
-         'N' => route.push(Direction::North),
-         'S' => route.push(Direction::South),
-         'E' => route.push(Direction::East),
-         'W' => route.push(Direction::West),
-         '(' =>
-         {
-            let mut crossing = Vec::new();
-            while regexp[0] != ')'
-            {
-               let (subroute, regexp_letf) = make_route(&regexp[1..]);
-               crossing.push(subroute);
-               regexp = regexp_letf;
-            }
-            route.push(Direction::Crossing(crossing))
-         }
-         _ => panic!("Unknown direction!")
+         Direction::Crossing(routes) => routes,
+         _ => &[]
       }
-      regexp = &regexp[1..];
    }
 
-   (route, regexp)
+   /// takes the routes out of a crossing, None for a single direction
+   fn take_routes(&mut self) -> Option<Vec<Route>>
+   {
+      match self
+      {
+         Direction::Crossing(routes) => Some(std::mem::take(routes)),
+         _ => None
+      }
+   }
 }
 
-/// applies a direction to a position and update the doors
-fn apply_direction((i, j): Position, direction: &Direction, doors: &mut HashSet<Position>) -> Position
+impl Drop for Direction
 {
-   match direction
+   /// moves the nested directions onto a flat list so that each direction is dropped without routes
+   fn drop(&mut self)
    {
-      Direction::East =>
+      let mut directions: Vec<Direction> = self.take_routes().into_iter().flatten().flatten().collect();
+      while let Some(mut direction) = directions.pop()
       {
-         doors.insert((i, j + 1));
-         (i, j + 2)
+         directions.extend(direction.take_routes().into_iter().flatten().flatten());
       }
-      Direction::North =>
+   }
+}
+
+impl PartialEq for Direction
+{
+   fn eq(&self, other: &Direction) -> bool
+   {
+      let mut pairs = vec![(self, other)];
+      while let Some((direction, other_direction)) = pairs.pop()
       {
-         doors.insert((i + 1, j));
-         (i + 2, j)
+         let (routes, other_routes) = (direction.routes(), other_direction.routes());
+         if std::mem::discriminant(direction) != std::mem::discriminant(other_direction)
+            || routes.len() != other_routes.len()
+            || routes.iter().zip(other_routes).any(|(route, other_route)| route.len() != other_route.len())
+         {
+            return false;
+         }
+         pairs.extend(routes.iter().flatten().zip(other_routes.iter().flatten()));
       }
-      Direction::South =>
+      true
+   }
+}
+
+//-----------------------------------------------------------------------------
+// ROUTE PARSING
+
+/// converts a syntax tree into a route
+/// the tree is walked with an explicit stack as regexes can be nested arbitrarily deep
+fn route_of_node(root: &Node) -> Route
+{
+   // each node waits for the routes of its children before being converted
+   let mut stack = vec![(root, root.children().iter(), Vec::new())];
+   loop
+   {
+      let (_, nodes, _) = stack.last_mut().expect("The root is the last node converted.");
+      if let Some(node) = nodes.next()
       {
-         doors.insert((i - 1, j));
-         (i - 2, j)
+         stack.push((node, node.children().iter(), Vec::new()));
+         continue;
       }
-      Direction::West =>
+
+      let (node, _, routes) = stack.pop().expect("The root is the last node converted.");
+      let route = match node
+      {
+         Node::Move(heading, _) => vec![match heading
+                                   {
+                                      Heading::North => Direction::North,
+                                      Heading::East => Direction::East,
+                                      Heading::South => Direction::South,
+                                      Heading::West => Direction::West
+                                   }],
+         Node::Sequence(..) => routes.into_iter().flatten().collect(),
+         Node::Alternation(..) => vec![Direction::Crossing(routes)],
+         Node::Empty(_) => Vec::new()
+      };
+      match stack.last_mut()
       {
-         doors.insert((i, j - 1));
-         (i, j - 2)
+         Some((_, _, parent_routes)) => parent_routes.push(route),
+         None => return route
       }
-      Direction::Crossing(_) => panic!("This direction cannot be applied")
    }
 }
 
-/// takes current positions, a route and doors seen so far
-/// returns new positions obtained after taking the route while updating the list of door seen
-fn apply_route(mut current_position: HashSet<Position>,
-               route: &Route,
-               mut doors: &mut HashSet<Position>)
-               -> HashSet<Position>
+/// removes the directions that end all the routes and returns them in order
+fn pop_common_suffix(routes: &mut [Route]) -> Vec<Direction>
+{
+   let mut suffix = Vec::new();
+   while routes.iter().all(|route| !route.is_empty())
+         && routes.iter().all(|route| route.last() == routes[0].last())
+   {
+      suffix.push(routes[0].pop().expect("The options are not empty."));
+      routes.iter_mut().skip(1).for_each(|route| {
+                                      route.pop();
+                                   });
+   }
+   suffix.reverse();
+   suffix
+}
+
+/// a route, or a crossing, being processed with an explicit stack
+enum Frame<D, R, T>
 {
-   for direction in route
+   /// the remaining directions of a route and what the route produced so far
+   Route(D, T),
+   /// the remaining options of a crossing and what each finished option produced
+   Crossing(R, Vec<T>)
+}
+
+/// moves the directions that end all the options of a crossing after the crossing
+/// `(NNE|SE)` becomes `(NN|S)E` so that the shared suffix is followed once from the merged positions
+/// (the route is walked with an explicit stack, like in `route_of_node`)
+fn share_suffixes(route: Route) -> Route
+{
+   let mut stack = vec![Frame::Route(route.into_iter(), Vec::new())];
+   loop
    {
-      match direction
+      match stack.last_mut().expect("The root route is the last one processed.")
       {
-         Direction::Crossing(routes) =>
+         Frame::Route(directions, result) => match directions.next()
          {
-            current_position =
-               routes.iter()
-                     .flat_map(|route| apply_route(current_position.clone(), &route, &mut doors))
-                     .collect();
-         }
-         _ =>
+            Some(mut direction) => match direction.take_routes()
+            {
+               Some(routes) => stack.push(Frame::Crossing(routes.into_iter(), Vec::new())),
+               None => result.push(direction)
+            },
+            None =>
+            {
+               let route = std::mem::take(result);
+               stack.pop();
+               match stack.last_mut()
+               {
+                  Some(Frame::Crossing(_, routes)) => routes.push(route),
+                  Some(Frame::Route(..)) => unreachable!("Routes are only found inside crossings."),
+                  None => return route
+               }
+            }
+         },
+         Frame::Crossing(options, routes) => match options.next()
          {
-            current_position =
-               current_position.iter()
-                               .map(|&position| apply_direction(position, direction, &mut doors))
-                               .collect();
+            Some(route) => stack.push(Frame::Route(route.into_iter(), Vec::new())),
+            None =>
+            {
+               let mut routes = std::mem::take(routes);
+               stack.pop();
+               let suffix = pop_common_suffix(&mut routes);
+               match stack.last_mut()
+               {
+                  Some(Frame::Route(_, result)) =>
+                  {
+                     result.push(Direction::Crossing(routes));
+                     result.extend(suffix);
+                  }
+                  _ => unreachable!("Crossings are only found inside routes.")
+               }
+            }
          }
       }
    }
+}
 
-   current_position
+/// parses a regex into a route
+fn make_route(regexp: &[char]) -> Result<Route, ParseError>
+{
+   regex::parse(regexp).map(|root| share_suffixes(route_of_node(&root)))
 }
 
-/// returns the position of the player and a map
-fn make_map(doors: &HashSet<Position>) -> ((usize, usize), Vec<Vec<Base>>)
+/// applies a direction to a position and adds the door that was taken to the facility
+fn apply_direction((i, j): Position, direction: &Direction, facility: &mut Facility) -> Position
 {
-   let imin = doors.iter().map(|(i, _)| i).min().unwrap();
-   let imax = doors.iter().map(|(i, _)| i).max().unwrap();
-   let jmin = doors.iter().map(|(_, j)| j).min().unwrap();
-   let jmax = doors.iter().map(|(_, j)| j).max().unwrap();
+   let destination = match direction
+   {
+      Direction::East => (i, j + 1),
+      Direction::North => (i + 1, j),
+      Direction::South => (i - 1, j),
+      Direction::West => (i, j - 1),
+      Direction::Crossing(_) => panic!("This direction cannot be applied")
+   };
+   facility.add_door((i, j), destination);
+   destination
+}
+
+/// returns the displacement of a route, None if it contains a crossing
+fn displacement(route: &Route) -> Option<Position>
+{
+   route.iter().try_fold((0, 0), |(i, j), direction| match direction
+   {
+      Direction::North => Some((i + 1, j)),
+      Direction::South => Some((i - 1, j)),
+      Direction::East => Some((i, j + 1)),
+      Direction::West => Some((i, j - 1)),
+      Direction::Crossing(_) => None
+   })
+}
+
+/// detects the detour pattern `(NEWS|)`: an empty option and options that come back to where they started
+/// such a crossing leaves the positions unchanged
+fn is_detour(routes: &[Route]) -> bool
+{
+   routes.iter().any(|route| route.is_empty())
+   && routes.iter().all(|route| displacement(route) == Some((0, 0)))
+}
+
+/// takes current positions (sorted and without duplicates), a route and the facility seen so far
+/// returns new positions obtained after taking the route while adding the doors seen to the facility
+///
+/// the positions reached by the options of a crossing are merged and deduplicated
+/// so that the rest of the route is followed only once from each position
+/// the options of the crossings are stacked explicitly, recursing on them would overflow on deep nestings
+fn apply_route(current_position: &[Position], route: &Route, facility: &mut Facility) -> Vec<Position>
+{
+   // a route holds the positions it reached, a crossing the positions reached by its finished options
+   let mut stack = vec![Frame::Route(route.iter(), current_position.to_vec())];
+   // positions at which the options of each crossing of the stack start
+   let mut starts: Vec<Vec<Position>> = Vec::new();
+
+   loop
+   {
+      match stack.last_mut().expect("The root route is the last one followed.")
+      {
+         Frame::Route(directions, current_position) => match directions.next()
+         {
+            Some(Direction::Crossing(routes)) if is_detour(routes) =>
+            {
+               // only the doors matter, the positions are unchanged
+               for route in routes
+               {
+                  for &start in current_position.iter()
+                  {
+                     let mut position = start;
+                     for direction in route
+                     {
+                        position = apply_direction(position, direction, facility);
+                     }
+                  }
+               }
+            }
+            Some(Direction::Crossing(routes)) =>
+            {
+               starts.push(std::mem::take(current_position));
+               stack.push(Frame::Crossing(routes.iter(), Vec::new()));
+            }
+            Some(direction) =>
+            {
+               // a translation keeps the positions sorted and distinct
+               for position in current_position.iter_mut()
+               {
+                  *position = apply_direction(*position, direction, facility);
+               }
+            }
+            None =>
+            {
+               let new_position = std::mem::take(current_position);
+               stack.pop();
+               match stack.last_mut()
+               {
+                  Some(Frame::Crossing(_, reached)) => reached.push(new_position),
+                  Some(Frame::Route(..)) => unreachable!("Routes are only found inside crossings."),
+                  None => return new_position
+               }
+            }
+         },
+         Frame::Crossing(options, reached) => match options.next()
+         {
+            Some(route) =>
+            {
+               let start = starts.last().expect("Each crossing has a start.").clone();
+               stack.push(Frame::Route(route.iter(), start));
+            }
+            None =>
+            {
+               let mut new_position: Vec<Position> = std::mem::take(reached).into_iter().flatten().collect();
+               new_position.sort_unstable();
+               new_position.dedup();
+               starts.pop();
+               stack.pop();
+               match stack.last_mut()
+               {
+                  Some(Frame::Route(_, current_position)) => *current_position = new_position,
+                  _ => unreachable!("Crossings are only found inside routes.")
+               }
+            }
+         }
+      }
+   }
+}
+
+/// returns the position of the player and a map of the facility
+/// rooms and doors alternate on the map so a room (i,j) is found at (2i,2j) before translation
+fn make_map(facility: &Facility) -> ((usize, usize), Vec<Vec<Base>>)
+{
+   // a facility without doors (`^$`) is reduced to the initial room
+   let doors = facility.doors();
+   let imin = doors.iter().map(|&(i, _)| i).min().unwrap_or(0);
+   let imax = doors.iter().map(|&(i, _)| i).max().unwrap_or(0);
+   let jmin = doors.iter().map(|&(_, j)| j).min().unwrap_or(0);
+   let jmax = doors.iter().map(|&(_, j)| j).max().unwrap_or(0);
 
    // find appropriate size
    let mut height = (3 + imax - imin) as usize;
-   if height % 2 == 0
+   if height.is_multiple_of(2)
    {
       height += 1;
    }
    let mut width = (3 + jmax - jmin) as usize;
-   if width % 2 == 0
+   if width.is_multiple_of(2)
    {
       width += 1;
    }
    let mut map = vec![vec![Base::Wall; width]; height];
    // place rooms
-   for i in 0..height
+   for row in map.iter_mut().skip(1).step_by(2)
    {
-      for j in 0..width
+      for base in row.iter_mut().skip(1).step_by(2)
       {
-         if (i % 2 == 1) && (j % 2 == 1)
-         {
-            map[i][j] = Base::Room;
-         }
+         *base = Base::Room;
       }
    }
    // place doors
@@ -190,75 +411,144 @@ fn display_map(map: &Map)
 }
 
 //-----------------------------------------------------------------------------
-// PATH
+// GRAPH
 
-/// makes a map of the distance from all points to (i,j)
-/// WARNING the distance is in number of square and needs to be divided by two to be converted in doors
-fn make_distance_map(initial_position: (usize, usize), map: &Map) -> Vec<Vec<usize>>
+impl Facility
 {
-   let height = map.len();
-   let width = map[0].len();
-   let max_dist = std::usize::MAX;
-   let mut distances = vec![vec![max_dist; width]; height];
-   let mut current_position = vec![initial_position];
-   let mut current_distance = 0;
-
-   while !current_position.is_empty()
+   /// adds a door in both directions
+   fn add_door(&mut self, room: Position, other_room: Position)
    {
-      let mut new_positions = Vec::new();
-      for (i, j) in current_position
+      for &(from, to) in [(room, other_room), (other_room, room)].iter()
       {
-         if (map[i][j] != Base::Wall) && (distances[i][j] > current_distance)
+         let neighbours = self.adjacency.entry(from).or_default();
+         if !neighbours.contains(&to)
          {
-            distances[i][j] = current_distance;
-            new_positions.push((i + 1, j));
-            new_positions.push((i - 1, j));
-            new_positions.push((i, j + 1));
-            new_positions.push((i, j - 1));
+            neighbours.push(to);
          }
       }
-      current_position = new_positions;
-      current_distance += 1;
    }
 
-   distances
-}
-
-fn farthest_room(initial_position: (usize, usize), map: &Map) -> usize
-{
-   let distances = make_distance_map(initial_position, &map);
-   distances.iter()
-            .flat_map(|row| row)
-            .filter(|&&dist| dist != std::usize::MAX)
-            .max()
-            .map(|dist| dist / 2)
-            .unwrap()
-}
-
-fn count_far_rooms(initial_position: (usize, usize), map: &Map, max_distance: usize) -> usize
-{
-   let height = map.len();
-   let width = map[0].len();
-   let illegal_dist = std::usize::MAX;
-   let distances = make_distance_map(initial_position, &map);
+   /// returns the position of every door in a coordinate system where rooms are at even coordinates
+   fn doors(&self) -> HashSet<Position>
+   {
+      self.adjacency
+          .iter()
+          .flat_map(|(&(i, j), rooms)| rooms.iter().map(move |(i2, j2)| (i + i2, j + j2)))
+          .collect()
+   }
 
-   let mut result = 0;
-   for i in 0..height
+   /// returns, for every reachable room, its distance in doors and the previous room on a shortest path
+   fn explore(&self, start: Position) -> HashMap<Position, (usize, Position)>
    {
-      for j in 0..width
+      let mut visited = HashMap::new();
+      visited.insert(start, (0, start));
+      let mut queue = VecDeque::new();
+      queue.push_back(start);
+
+      while let Some(room) = queue.pop_front()
       {
-         if map[i][j] == Base::Room
+         let distance = visited[&room].0;
+         for &neighbour in self.adjacency.get(&room).into_iter().flatten()
          {
-            let dist = distances[i][j];
-            if (dist != illegal_dist) && (dist / 2 >= max_distance)
+            if let Entry::Vacant(entry) = visited.entry(neighbour)
             {
-               result += 1;
+               entry.insert((distance + 1, room));
+               queue.push_back(neighbour);
             }
          }
       }
+
+      visited
    }
 
-   result
+   /// returns the distance, in doors, from the start to every reachable room
+   fn distances(&self, start: Position) -> HashMap<Position, usize>
+   {
+      self.explore(start).into_iter().map(|(room, (distance, _))| (room, distance)).collect()
+   }
+
+   /// returns the farthest room and its distance in doors
+   fn farthest_room(&self, start: Position) -> (Position, usize)
+   {
+      self.distances(start).into_iter().max_by_key(|&(room, distance)| (distance, room)).unwrap()
+   }
+
+   /// returns the rooms that are at least `min_distance` doors away
+   fn rooms_beyond(&self, start: Position, min_distance: usize) -> Vec<Position>
+   {
+      self.distances(start)
+          .into_iter()
+          .filter(|&(_, distance)| distance >= min_distance)
+          .map(|(room, _)| room)
+          .collect()
+   }
+
+   /// returns the rooms on a shortest path from start to goal (both included)
+   fn shortest_path(&self, start: Position, goal: Position) -> Option<Vec<Position>>
+   {
+      let visited = self.explore(start);
+      if !visited.contains_key(&goal)
+      {
+         return None;
+      }
+
+      let mut path = vec![goal];
+      let mut room = goal;
+      while room != start
+      {
+         room = visited[&room].1;
+         path.push(room);
+      }
+      path.reverse();
+      Some(path)
+   }
+}
+
+/// turns a path into the directions to follow
+fn directions_of_path(path: &[Position]) -> String
+{
+   path.windows(2)
+       .map(|rooms| match (rooms[1].0 - rooms[0].0, rooms[1].1 - rooms[0].1)
+       {
+          (1, 0) => 'N',
+          (-1, 0) => 'S',
+          (0, 1) => 'E',
+          (0, -1) => 'W',
+          _ => panic!("The rooms of a path should be adjacent!")
+       })
+       .collect()
+}
+
+//-----------------------------------------------------------------------------
+// BENCHMARK
+
+/// synthetic regexes with a lot of branching
+fn synthetic_regexes() -> Vec<(String, String)>
+{
+   let k = 100;
+   vec![("successive crossings".to_string(), format!("^{}$", "(N|E|S|W)".repeat(k / 2))),
+        ("detours".to_string(), format!("^{}$", "(NEWS|)E(WNSE|SNNS|)N".repeat(50 * k))),
+        ("nested crossings".to_string(), format!("^{}{}$", "(N|E".repeat(10 * k), ")".repeat(10 * k))),
+        ("detours after branching".to_string(),
+         format!("^{}{}$", "(N|S|E|W)".repeat(k / 4), "(NESW|)(EN(WNSE|)WS|)".repeat(10 * k))),
+        ("shared suffixes".to_string(), format!("^{}$", "(NNEEE|SEEE|WNEEEE)".repeat(k)))]
+}
+
+/// times the parsing and the evaluation of a regex
+fn benchmark_evaluation(name: &str, regexp: &str)
+{
+   let start = Instant::now();
+   let regexp: Vec<char> = regexp.chars().collect();
+   let route = make_route(&regexp).unwrap_or_else(|error| panic!("{}", error));
+   let mut facility = Facility::default();
+   let nb_positions = apply_route(&[(0, 0)], &route, &mut facility).len();
+   let time = start.elapsed();
+
+   println!("{:>24} : {:>6} rooms, {:>6} final positions, {:?}",
+            name,
+            facility.adjacency.len(),
+            nb_positions,
+            time);
 }
 
 //-----------------------------------------------------------------------------
@@ -267,23 +557,236 @@ fn count_far_rooms(initial_position: (usize, usize), map: &Map, max_distance: us
 fn main()
 {
    let input_path = "./data/input.txt";
-   let regexp: Vec<char> = std::fs::read_to_string(input_path).unwrap().trim_end().chars().collect();
+   let text = std::fs::read_to_string(input_path).expect("Failed to read the regex.");
+   let regexp: Vec<char> = text.trim_end().chars().collect();
+
+   // building the facility
+   let route = match make_route(&regexp)
+   {
+      Ok(route) => route,
+      Err(error) =>
+      {
+         println!("{}", error.report(text.trim_end()));
+         return;
+      }
+   };
+   let mut facility = Facility::default();
+   let start = (0, 0);
+   apply_route(&[start], &route, &mut facility);
+   println!("the facility has {} rooms", facility.adjacency.len());
 
-   // building the map
-   let (route, _) = make_route(&regexp);
-   let mut doors = HashSet::new();
-   let mut current_position = HashSet::new();
-   current_position.insert((0, 0));
-   apply_route(current_position, &route, &mut doors);
-   let (initial_position, map) = make_map(&doors);
-   display_map(&map);
+   // the map is an optional view
+   if std::env::args().any(|arg| arg == "--map")
+   {
+      let (_, map) = make_map(&facility);
+      display_map(&map);
+   }
 
    // task1
-   let distance_to_room = farthest_room(initial_position, &map);
+   let (farthest_room, distance_to_room) = facility.farthest_room(start);
    println!("the farthest room is at {} doors", distance_to_room);
+   let path = facility.shortest_path(start, farthest_room).expect("The farthest room should be reachable.");
+   println!("it can be reached by ^{}$", directions_of_path(&path));
 
    // task2
    let max_dist = 1000;
-   let nb_rooms = count_far_rooms(initial_position, &map, max_dist);
+   let nb_rooms = facility.rooms_beyond(start, max_dist).len();
    println!("number of rooms further than {} doors : {}", max_dist, nb_rooms);
+
+   // timing of the evaluation, on request
+   if std::env::args().any(|arg| arg == "--bench")
+   {
+      let paths = ["./data/test_18.txt", "./data/test_23.txt", "./data/test_31.txt", "./data/input.txt"];
+      for path in paths.iter()
+      {
+         let text = std::fs::read_to_string(path).expect("Failed to read the regex.");
+         benchmark_evaluation(path, text.trim_end());
+      }
+      for (name, regexp) in synthetic_regexes()
+      {
+         benchmark_evaluation(&name, &regexp);
+      }
+   }
+}
+
+//-----------------------------------------------------------------------------
+// TESTS
+
+#[cfg(test)]
+mod tests
+{
+   use super::*;
+   use regex::Span;
+
+   /// parses the given files and prints them back, the result should be the original regex
+   #[test]
+   fn canonical_form()
+   {
+      let paths = ["./data/input.txt",
+                   "./data/test.txt",
+                   "./data/test2.txt",
+                   "./data/test_18.txt",
+                   "./data/test_23.txt",
+                   "./data/test_31.txt"];
+      for path in paths.iter()
+      {
+         let text = std::fs::read_to_string(path).expect("Failed to read the regex.");
+         let regexp: Vec<char> = text.trim_end().chars().collect();
+         let root = regex::parse(&regexp).unwrap_or_else(|error| panic!("{}", error.report(text.trim_end())));
+         assert_eq!(regex::to_regex(&root), text.trim_end(), "{} is not printed back identically!", path);
+         assert_eq!(root.span(), Span { start: 1, end: regexp.len() - 1 }, "{} has a wrong span!", path);
+      }
+   }
+
+   /// invalid regexes should be rejected with the position of the error
+   #[test]
+   fn errors()
+   {
+      let invalid = [("ENWW$", ParseError::MissingCaret(0)),
+                     ("^ENWW", ParseError::MissingDollar(5)),
+                     ("^EN^WW$", ParseError::MisplacedCaret(3)),
+                     ("^EN(W$|S)$", ParseError::MisplacedDollar(5)),
+                     ("^EN(W|S(N|E)$", ParseError::UnclosedParenthesis(3)),
+                     ("^EN(W|S))N$", ParseError::UnopenedParenthesis(8)),
+                     ("^EN|W$", ParseError::BarOutsideParenthesis(3)),
+                     ("^ENXW$", ParseError::UnknownChar('X', 3)),
+                     ("^EN$W", ParseError::TrailingChars(4))];
+
+      for (text, expected) in invalid.iter()
+      {
+         let regexp: Vec<char> = text.chars().collect();
+         match make_route(&regexp)
+         {
+            Ok(_) => panic!("{} should have been rejected!", text),
+            Err(error) => assert_eq!(&error, expected, "wrong error for {}", text)
+         }
+      }
+
+      let (text, error) = &invalid[4];
+      let report = "^EN(W|S(N|E)$\n   ^ this parenthesis is never closed (char 3)";
+      assert_eq!(error.report(text), report);
+   }
+
+   /// the shortest path to the farthest room goes through as many doors as its distance
+   #[test]
+   fn path_to_farthest_room()
+   {
+      let paths = ["./data/test.txt", "./data/test2.txt", "./data/test_31.txt", "./data/input.txt"];
+      for path in paths.iter()
+      {
+         let text = std::fs::read_to_string(path).expect("Failed to read the regex.");
+         let regexp: Vec<char> = text.trim_end().chars().collect();
+         let route = make_route(&regexp).unwrap_or_else(|error| panic!("{}", error));
+         let mut facility = Facility::default();
+         apply_route(&[(0, 0)], &route, &mut facility);
+
+         let (farthest_room, distance_to_room) = facility.farthest_room((0, 0));
+         let rooms = facility.shortest_path((0, 0), farthest_room).expect("Unreachable room.");
+         assert_eq!(rooms.len(), distance_to_room + 1, "{} : the path has the wrong length!", path);
+         assert_eq!(rooms.first(), Some(&(0, 0)));
+         assert_eq!(rooms.last(), Some(&farthest_room));
+      }
+   }
+
+   /// evaluates a regex with and without sharing the suffixes of the crossings
+   /// both should give the same facility and the same final positions
+   fn check_shared_suffixes(name: &str, regexp: &str)
+   {
+      let regexp: Vec<char> = regexp.chars().collect();
+      let root = regex::parse(&regexp).unwrap_or_else(|error| panic!("{}", error));
+
+      let mut facility = Facility::default();
+      let positions = apply_route(&[(0, 0)], &route_of_node(&root), &mut facility);
+      let mut facility_shared = Facility::default();
+      let route_shared = share_suffixes(route_of_node(&root));
+      let positions_shared = apply_route(&[(0, 0)], &route_shared, &mut facility_shared);
+
+      assert!(positions == positions_shared, "{}: the final positions differ!", name);
+      assert!(facility.doors() == facility_shared.doors(), "{}: the facilities differ!", name);
+   }
+
+   #[test]
+   fn shared_suffixes()
+   {
+      let regexp: Vec<char> = "^(NNE|SE|E(N|S)E)W$".chars().collect();
+      let route = make_route(&regexp).expect("Valid regex.");
+      let regexp_shared: Vec<char> = "^(NN|S|E(N|S))EW$".chars().collect();
+      let expected = route_of_node(&regex::parse(&regexp_shared).expect("Valid regex."));
+      assert!(route == expected, "The suffix was not shared!");
+
+      let paths = ["./data/test_18.txt", "./data/test_23.txt", "./data/test_31.txt", "./data/input.txt"];
+      for path in paths.iter()
+      {
+         let text = std::fs::read_to_string(path).expect("Failed to read the regex.");
+         check_shared_suffixes(path, text.trim_end());
+      }
+      for (name, regexp) in synthetic_regexes()
+      {
+         check_shared_suffixes(&name, &regexp);
+      }
+   }
+
+   /// parses, compares, evaluates and drops a regex nested far deeper than the call stack could follow
+   #[test]
+   fn deeply_nested_regex()
+   {
+      let depth = 10_000;
+      let text = format!("^{}{}$", "(WE|N".repeat(depth), "S)".repeat(depth));
+      let regexp: Vec<char> = text.chars().collect();
+      let root = regex::parse(&regexp).expect("Valid regex.");
+      assert!(root == regex::parse(&regexp).expect("Valid regex."), "The regex differs from itself!");
+      let route = share_suffixes(route_of_node(&root));
+      drop(root);
+      assert!(route == share_suffixes(route_of_node(&regex::parse(&regexp).expect("Valid regex."))));
+
+      // `(WE|N(WE|N...S)S)` visits a column of rooms and their west neighbours, then comes back
+      let mut facility = Facility::default();
+      let positions = apply_route(&[(0, 0)], &route, &mut facility);
+      drop(route);
+      assert_eq!(positions, vec![(0, 0)]);
+      assert_eq!(facility.adjacency.len(), 2 * depth + 1);
+      assert_eq!(facility.farthest_room((0, 0)).1, depth);
+   }
+
+   /// an empty route has no doors, its map is reduced to the initial room
+   #[test]
+   fn empty_route_map()
+   {
+      let regexp: Vec<char> = "^$".chars().collect();
+      let route = make_route(&regexp).expect("An empty route is valid.");
+      let mut facility = Facility::default();
+      apply_route(&[(0, 0)], &route, &mut facility);
+      let (initial_position, map) = make_map(&facility);
+      assert_eq!(initial_position, (1, 1));
+      assert!(map
+         == vec![vec![Base::Wall; 3], vec![Base::Wall, Base::InitialPosition, Base::Wall], vec![Base::Wall; 3]]);
+   }
+
+   /// generates random mazes, turns them into regexes and parses them back
+   /// the facility found should be the maze, with the farthest room known from the generation
+   #[test]
+   fn random_mazes()
+   {
+      let sizes =
+         [(1, 1, 0), (1, 1, 3), (2, 1, 0), (5, 5, 0), (5, 5, 10), (20, 10, 0), (30, 30, 50), (60, 40, 200)];
+      for &(width, height, nb_loops) in sizes.iter()
+      {
+         for seed in 1..=10
+         {
+            let maze = maze::random_maze(width, height, nb_loops, seed);
+            let (text, farthest_distance) = maze::regex_of_facility(&maze, (0, 0));
+            let regexp: Vec<char> = text.chars().collect();
+            let route = make_route(&regexp).unwrap_or_else(|error| panic!("{}", error.report(&text)));
+
+            let mut facility = Facility::default();
+            facility.adjacency.insert((0, 0), Vec::new());
+            apply_route(&[(0, 0)], &route, &mut facility);
+            assert_eq!(facility.adjacency.len(), width * height, "some rooms were not visited by {}", text);
+            assert!(facility.doors() == maze.doors(), "the doors do not match for {}", text);
+            let (_, distance) = facility.farthest_room((0, 0));
+            assert_eq!(distance, farthest_distance, "wrong farthest room for {}", text);
+            assert!(make_map(&facility) == make_map(&maze), "the maps do not match for {}", text);
+         }
+      }
+   }
 }
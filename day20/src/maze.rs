@@ -0,0 +1,177 @@
+use crate::{Facility, Position};
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, VecDeque};
+
+//-----------------------------------------------------------------------------
+// GENERATOR
+
+/// a xorshift pseudo-random generator
+struct Random
+{
+   state: u64
+}
+
+impl Random
+{
+   fn new(seed: u64) -> Random
+   {
+      Random { state: seed.max(1) }
+   }
+
+   /// returns a number in 0..n
+   fn below(&mut self, n: usize) -> usize
+   {
+      self.state ^= self.state << 13;
+      self.state ^= self.state >> 7;
+      self.state ^= self.state << 17;
+      (self.state % n as u64) as usize
+   }
+}
+
+/// the rooms next to a room, inside a maze of the given size
+fn neighbours((i, j): Position, width: usize, height: usize) -> Vec<Position>
+{
+   let is_inside = |&(i, j): &Position| i >= 0 && j >= 0 && (i as usize) < height && (j as usize) < width;
+   [(i + 1, j), (i - 1, j), (i, j + 1), (i, j - 1)].iter().cloned().filter(is_inside).collect()
+}
+
+/// generates a random maze of width*height rooms, starting at (0,0)
+/// the rooms are first connected by a random depth first search then `nb_loops` random doors are added
+pub fn random_maze(width: usize, height: usize, nb_loops: usize, seed: u64) -> Facility
+{
+   let mut random = Random::new(seed);
+   let mut facility = Facility::default();
+   let start = (0, 0);
+   facility.adjacency.insert(start, Vec::new());
+
+   let mut stack = vec![start];
+   while let Some(&room) = stack.last()
+   {
+      let is_unvisited = |n: &Position| !facility.adjacency.contains_key(n);
+      let unvisited: Vec<_> = neighbours(room, width, height).into_iter().filter(is_unvisited).collect();
+      if unvisited.is_empty()
+      {
+         stack.pop();
+      }
+      else
+      {
+         let next = unvisited[random.below(unvisited.len())];
+         facility.add_door(room, next);
+         stack.push(next);
+      }
+   }
+
+   for _ in 0..nb_loops
+   {
+      // a maze of a single room has no door to add
+      let room = (random.below(height) as i32, random.below(width) as i32);
+      let candidates = neighbours(room, width, height);
+      if !candidates.is_empty()
+      {
+         facility.add_door(room, candidates[random.below(candidates.len())]);
+      }
+   }
+
+   facility
+}
+
+//-----------------------------------------------------------------------------
+// REGEX
+
+/// the letter of the door that leads from a room to the next
+fn heading(from: Position, to: Position) -> char
+{
+   match (to.0 - from.0, to.1 - from.1)
+   {
+      (1, 0) => 'N',
+      (-1, 0) => 'S',
+      (0, 1) => 'E',
+      (0, -1) => 'W',
+      _ => panic!("The rooms should be adjacent!")
+   }
+}
+
+/// writes the exploration of the tree below a room
+/// corridors are written one after the other, only branching rooms recurse
+fn write_branch(mut room: Position,
+                children: &HashMap<Position, Vec<Position>>,
+                detours: &HashMap<Position, Vec<Position>>,
+                regexp: &mut String)
+{
+   loop
+   {
+      for &other in detours.get(&room).into_iter().flatten()
+      {
+         regexp.push('(');
+         regexp.push(heading(room, other));
+         regexp.push(heading(other, room));
+         regexp.push_str("|)");
+      }
+
+      match children.get(&room).map(|c| c.as_slice()).unwrap_or(&[])
+      {
+         [] => return,
+         [child] =>
+         {
+            regexp.push(heading(room, *child));
+            room = *child;
+         }
+         branches =>
+         {
+            regexp.push('(');
+            for (k, &child) in branches.iter().enumerate()
+            {
+               if k > 0
+               {
+                  regexp.push('|');
+               }
+               regexp.push(heading(room, child));
+               write_branch(child, children, detours, regexp);
+            }
+            regexp.push(')');
+            return;
+         }
+      }
+   }
+}
+
+/// returns a regex that goes through every door of the facility and the distance to its farthest room
+/// the regex follows a breadth first search tree so that its branches are shortest paths
+/// doors outside of the tree are taken as detours `(NS|)`
+pub fn regex_of_facility(facility: &Facility, start: Position) -> (String, usize)
+{
+   let mut distances = HashMap::new();
+   distances.insert(start, 0);
+   let mut parents = HashMap::new();
+   let mut children: HashMap<Position, Vec<Position>> = HashMap::new();
+   let mut detours: HashMap<Position, Vec<Position>> = HashMap::new();
+   let mut queue = VecDeque::new();
+   queue.push_back(start);
+
+   while let Some(room) = queue.pop_front()
+   {
+      let distance = distances[&room] + 1;
+      let mut rooms = facility.adjacency[&room].clone();
+      rooms.sort_unstable();
+      for other in rooms
+      {
+         if let Entry::Vacant(entry) = distances.entry(other)
+         {
+            entry.insert(distance);
+            parents.insert(other, room);
+            children.entry(room).or_default().push(other);
+            queue.push_back(other);
+         }
+         else if room < other && parents.get(&room) != Some(&other) && parents.get(&other) != Some(&room)
+         {
+            // a door outside of the tree, written once from one of its sides
+            detours.entry(room).or_default().push(other);
+         }
+      }
+   }
+
+   let mut regexp = String::from("^");
+   write_branch(start, &children, &detours, &mut regexp);
+   regexp.push('$');
+   (regexp, distances.values().cloned().max().unwrap_or(0))
+}
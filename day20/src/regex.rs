@@ -0,0 +1,324 @@
+use std::fmt;
+
+//-----------------------------------------------------------------------------
+// TYPES
+
+/// a range of chars in the regex, `start` included and `end` excluded
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Span
+{
+   pub start: usize,
+   pub end: usize
+}
+
+/// a direction that goes through a single door
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Heading
+{
+   North,
+   East,
+   South,
+   West
+}
+
+/// the syntax tree of a route regex
+/// it is dropped and compared with explicit stacks as regexes can be nested arbitrarily deep
+#[derive(Debug)]
+pub enum Node
+{
+   /// goes through a door
+   Move(Heading, Span),
+   /// nodes followed one after the other
+   Sequence(Vec<Node>, Span),
+   /// options between parenthesis, separated by '|'
+   Alternation(Vec<Node>, Span),
+   /// an option without any move, as in `(NEWS|)`
+   Empty(Span)
+}
+
+/// what can go wrong while parsing a regex, with the position of the faulty char
+#[derive(Debug, PartialEq)]
+pub enum ParseError
+{
+   MissingCaret(usize),
+   MissingDollar(usize),
+   MisplacedCaret(usize),
+   MisplacedDollar(usize),
+   UnclosedParenthesis(usize),
+   UnopenedParenthesis(usize),
+   BarOutsideParenthesis(usize),
+   UnknownChar(char, usize),
+   TrailingChars(usize)
+}
+
+//-----------------------------------------------------------------------------
+// NODE
+
+impl Heading
+{
+   fn of_char(c: char) -> Option<Heading>
+   {
+      match c
+      {
+         'N' => Some(Heading::North),
+         'E' => Some(Heading::East),
+         'S' => Some(Heading::South),
+         'W' => Some(Heading::West),
+         _ => None
+      }
+   }
+
+   #[cfg(test)]
+   pub fn to_char(self) -> char
+   {
+      match self
+      {
+         Heading::North => 'N',
+         Heading::East => 'E',
+         Heading::South => 'S',
+         Heading::West => 'W'
+      }
+   }
+}
+
+impl Node
+{
+   /// the nodes found inside a node
+   pub fn children(&self) -> &[Node]
+   {
+      match self
+      {
+         Node::Sequence(nodes, _) | Node::Alternation(nodes, _) => nodes,
+         _ => &[]
+      }
+   }
+
+   #[cfg(test)]
+   pub fn span(&self) -> Span
+   {
+      match self
+      {
+         Node::Move(_, span) | Node::Sequence(_, span) | Node::Alternation(_, span) | Node::Empty(span) =>
+         {
+            *span
+         }
+      }
+   }
+}
+
+impl Drop for Node
+{
+   /// moves the descendants onto a flat list so that each node is dropped without children
+   fn drop(&mut self)
+   {
+      let mut nodes = Vec::new();
+      if let Node::Sequence(children, _) | Node::Alternation(children, _) = self
+      {
+         nodes = std::mem::take(children);
+      }
+      while let Some(mut node) = nodes.pop()
+      {
+         if let Node::Sequence(children, _) | Node::Alternation(children, _) = &mut node
+         {
+            nodes.append(children);
+         }
+      }
+   }
+}
+
+impl PartialEq for Node
+{
+   fn eq(&self, other: &Node) -> bool
+   {
+      let mut pairs = vec![(self, other)];
+      while let Some(pair) = pairs.pop()
+      {
+         let same_node = match pair
+         {
+            (Node::Move(heading, span), Node::Move(other_heading, other_span)) =>
+            {
+               heading == other_heading && span == other_span
+            }
+            (Node::Sequence(_, span), Node::Sequence(_, other_span))
+            | (Node::Alternation(_, span), Node::Alternation(_, other_span))
+            | (Node::Empty(span), Node::Empty(other_span)) => span == other_span,
+            _ => false
+         };
+         let (node, other_node) = pair;
+         if !same_node || node.children().len() != other_node.children().len()
+         {
+            return false;
+         }
+         pairs.extend(node.children().iter().zip(other_node.children()));
+      }
+      true
+   }
+}
+
+/// prints the node back as a regex (without the '^' and '$' anchors)
+#[cfg(test)]
+impl fmt::Display for Node
+{
+   fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+   {
+      match self
+      {
+         Node::Move(heading, _) => write!(f, "{}", heading.to_char()),
+         Node::Sequence(nodes, _) => nodes.iter().try_for_each(|node| write!(f, "{}", node)),
+         Node::Alternation(options, _) =>
+         {
+            write!(f, "(")?;
+            for (i, option) in options.iter().enumerate()
+            {
+               if i > 0
+               {
+                  write!(f, "|")?;
+               }
+               write!(f, "{}", option)?;
+            }
+            write!(f, ")")
+         }
+         Node::Empty(_) => Ok(())
+      }
+   }
+}
+
+/// prints a full regex, with its anchors
+#[cfg(test)]
+pub fn to_regex(root: &Node) -> String
+{
+   format!("^{}$", root)
+}
+
+//-----------------------------------------------------------------------------
+// PARSER
+
+impl ParseError
+{
+   /// position of the faulty char in the regex
+   pub fn position(&self) -> usize
+   {
+      match self
+      {
+         ParseError::MissingCaret(p)
+         | ParseError::MissingDollar(p)
+         | ParseError::MisplacedCaret(p)
+         | ParseError::MisplacedDollar(p)
+         | ParseError::UnclosedParenthesis(p)
+         | ParseError::UnopenedParenthesis(p)
+         | ParseError::BarOutsideParenthesis(p)
+         | ParseError::UnknownChar(_, p)
+         | ParseError::TrailingChars(p) => *p
+      }
+   }
+
+   /// displays the regex with the error underlined
+   pub fn report(&self, regexp: &str) -> String
+   {
+      format!("{}\n{}^ {}", regexp, " ".repeat(self.position()), self)
+   }
+}
+
+impl fmt::Display for ParseError
+{
+   fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+   {
+      match self
+      {
+         ParseError::MissingCaret(p) => write!(f, "the regex should start with '^' (char {})", p),
+         ParseError::MissingDollar(p) => write!(f, "the regex should end with '$' (char {})", p),
+         ParseError::MisplacedCaret(p) => write!(f, "'^' can only start the regex (char {})", p),
+         ParseError::MisplacedDollar(p) => write!(f, "'$' can only end the regex (char {})", p),
+         ParseError::UnclosedParenthesis(p) => write!(f, "this parenthesis is never closed (char {})", p),
+         ParseError::UnopenedParenthesis(p) => write!(f, "this parenthesis was never opened (char {})", p),
+         ParseError::BarOutsideParenthesis(p) => write!(f, "'|' should be inside parenthesis (char {})", p),
+         ParseError::UnknownChar(c, p) => write!(f, "unknown char '{}' (char {})", c, p),
+         ParseError::TrailingChars(p) => write!(f, "nothing should follow '$' (char {})", p)
+      }
+   }
+}
+
+/// a sequence being parsed, `open` is the position of the '(' of its alternation (None for the root)
+/// the options of the alternation that are already parsed are kept along with the current one
+struct Frame
+{
+   open: Option<usize>,
+   options: Vec<Node>,
+   nodes: Vec<Node>,
+   start: usize
+}
+
+impl Frame
+{
+   /// closes the current option of the alternation at `end` (an empty option becomes an `Empty` node)
+   fn close_option(&mut self, end: usize)
+   {
+      let span = Span { start: self.start, end };
+      let nodes = std::mem::take(&mut self.nodes);
+      self.options.push(if nodes.is_empty() { Node::Empty(span) } else { Node::Sequence(nodes, span) });
+   }
+}
+
+/// parses a full regex, from '^' to '$'
+/// the alternations that are still open are kept on an explicit stack
+/// as regexes can be nested arbitrarily deep
+pub fn parse(regexp: &[char]) -> Result<Node, ParseError>
+{
+   if regexp.first() != Some(&'^')
+   {
+      return Err(ParseError::MissingCaret(0));
+   }
+
+   let mut stack = vec![Frame { open: None, options: Vec::new(), nodes: Vec::new(), start: 1 }];
+   let mut position = 1;
+   loop
+   {
+      let frame = stack.last_mut().expect("The root sequence is never closed.");
+      match (regexp.get(position), frame.open)
+      {
+         (Some('('), _) =>
+         {
+            let start = position + 1;
+            stack.push(Frame { open: Some(position), options: Vec::new(), nodes: Vec::new(), start });
+         }
+         (Some('|'), Some(_)) =>
+         {
+            frame.close_option(position);
+            frame.start = position + 1;
+         }
+         (Some(')'), Some(open)) =>
+         {
+            frame.close_option(position);
+            let options = std::mem::take(&mut frame.options);
+            stack.pop();
+            let alternation = Node::Alternation(options, Span { start: open, end: position + 1 });
+            stack.last_mut().expect("An alternation is always inside a sequence.").nodes.push(alternation);
+         }
+         // a '$' that ends the regex means that the parenthesis was not closed
+         (Some('$'), Some(_)) if position + 1 < regexp.len() =>
+         {
+            return Err(ParseError::MisplacedDollar(position))
+         }
+         (Some('$'), Some(open)) | (None, Some(open)) => return Err(ParseError::UnclosedParenthesis(open)),
+         (Some('$'), None) if position + 1 == regexp.len() =>
+         {
+            let nodes = std::mem::take(&mut frame.nodes);
+            return Ok(Node::Sequence(nodes, Span { start: 1, end: position }));
+         }
+         (Some('$'), None) => return Err(ParseError::TrailingChars(position + 1)),
+         (Some(')'), None) => return Err(ParseError::UnopenedParenthesis(position)),
+         (Some('|'), None) => return Err(ParseError::BarOutsideParenthesis(position)),
+         (None, None) => return Err(ParseError::MissingDollar(position)),
+         (Some('^'), _) => return Err(ParseError::MisplacedCaret(position)),
+         (Some(&c), _) => match Heading::of_char(c)
+         {
+            Some(heading) =>
+            {
+               frame.nodes.push(Node::Move(heading, Span { start: position, end: position + 1 }))
+            }
+            None => return Err(ParseError::UnknownChar(c, position))
+         }
+      }
+      position += 1;
+   }
+}
@@ -4,14 +4,18 @@ use std::fs::File;
 use std::i32;
 use std::io::{BufRead, BufReader};
 extern crate priority_queue;
+mod query;
 use priority_queue::PriorityQueue;
+use std::cmp::Reverse;
+use geometry::{Ball, Cuboid, Metric, Point};
+use query::Swarm;
 
 //-----------------------------------------------------------------------------
 // TYPE
 
 type Coordinate = i32;
 
-type Position = (Coordinate, Coordinate, Coordinate);
+type Position = Point<3>;
 
 #[derive(Copy, Clone)]
 struct Nanobot
@@ -26,7 +30,7 @@ struct Nanobot
 fn parse_nanobot(line: &str) -> Nanobot
 {
    let (x, y, z, radius) = scan_fmt!(line, "pos=<{},{},{}>, r={}", i32, i32, i32, i32);
-   let position = (x.unwrap(), y.unwrap(), z.unwrap());
+   let position = Point::new([x.unwrap(), y.unwrap(), z.unwrap()]);
    let radius = radius.unwrap();
    Nanobot { position, radius }
 }
@@ -44,14 +48,15 @@ fn input_data(path: &str) -> Vec<Nanobot>
 //-----------------------------------------------------------------------------
 // TASK1
 
-fn manhatan_distance((x1, y1, z1): Position, (x2, y2, z2): Position) -> i32
+/// the cells in range of the bot
+fn range(bot: &Nanobot) -> Ball<3>
 {
-   (x1 - x2).abs() + (y1 - y2).abs() + (z1 - z2).abs()
+   Ball::manhattan(bot.position, bot.radius)
 }
 
 fn is_in_range(bot: &Nanobot, position_target: Position) -> bool
 {
-   manhatan_distance(bot.position, position_target) <= bot.radius
+   range(bot).contains(&position_target)
 }
 
 fn task1(nanobots: &[Nanobot]) -> usize
@@ -68,192 +73,94 @@ fn task1(nanobots: &[Nanobot]) -> usize
 #[derive(Clone, Hash, Eq, PartialEq)]
 struct Cube
 {
-   xmin: i32,
-   xmax: i32,
-   ymin: i32,
-   ymax: i32,
-   zmin: i32,
-   zmax: i32,
+   cuboid: Cuboid<3>,
    bots_in_range: Vec<usize> // vector of indexes of bots such that at least a cell of the cube is in range
 }
 
-/// computes the shortest manhatan distance between a cube and a position
-fn manhatan_distance_cube(cube: &Cube, (x, y, z): Position) -> i32
+/// largest absolute coordinate of a set of points
+fn max_coordinate<'a, I: Iterator<Item = &'a Position>>(points: I) -> i32
 {
-   /// distance between a segment and a value
-   fn dist(kmin: i32, kmax: i32, k: i32) -> i32
-   {
-      if k < kmin
-      {
-         kmin - k
-      }
-      else if k >= kmax
-      {
-         k - kmax + 1 // because kmax is excluded
-      }
-      else
-      {
-         0 // k is in [kmin;kmax]
-      }
-   }
-   let dx = dist(cube.xmin, cube.xmax, x);
-   let dy = dist(cube.ymin, cube.ymax, y);
-   let dz = dist(cube.zmin, cube.zmax, z);
-   dx + dy + dz
+   points.flat_map(|p| p.0.iter()).map(|k| k.abs()).max().unwrap_or(0)
 }
 
-/// is at least a cell of the cube in range of the bot
-fn is_cube_in_range(cube: &Cube, bot: &Nanobot) -> bool
+/// builds a cube whose dimensions are power of two and that contains all coordinates up to `max_coordinate`
+fn make_cube(bots: &[Nanobot], max_coordinate: i32) -> Cube
 {
-   manhatan_distance_cube(&cube, bot.position) <= bot.radius
+   // the max is excluded so a point at +max_coordinate would not be inside a cube of size 2*max_coordinate
+   let width = (max_coordinate as usize + 1).next_power_of_two() as i32;
+   // builds the cube
+   let bots_in_range = (0..bots.len()).collect();
+   Cube { cuboid: Cuboid::centered(width), bots_in_range }
 }
 
-/// returns true if the cube collapsed into a cell
-fn is_cell(cube: &Cube) -> bool
+/// takes a cube and splits it in 8 sub cubes
+/// each cube gets the bots that are in its range
+fn subdivide_cube(cube: &Cube, bots: &[Nanobot]) -> Vec<Cube>
 {
-   (cube.xmin == cube.xmax - 1) && (cube.ymin == cube.ymax - 1) && (cube.zmin == cube.zmax - 1)
+   cube.cuboid
+       .subdivide()
+       .into_iter()
+       .map(|cuboid| {
+          let bots_in_range =
+             cube.bots_in_range.iter().filter(|&&i| range(&bots[i]).intersects(&cuboid)).cloned().collect();
+          Cube { cuboid, bots_in_range }
+       })
+       .collect()
 }
 
-/// builds a cube whose dimensions are power of two and that contains all the bots
-fn make_cube(bots: &[Nanobot]) -> Cube
+/// the order in which cubes are explored : more bots in range, then closer to the origin, then smaller
+/// the bots and distance of a cube bound those of its cells so the first cell out of the queue is optimal
+type Priority = (usize, Reverse<i32>, Reverse<i32>);
+
+fn priority(cube: &Cube) -> Priority
 {
-   // finds dimenssions of the box
-   let width = bots.iter()
-                   .flat_map(|b| vec![b.position.0, b.position.1, b.position.2])
-                   .map(|k| k.abs())
-                   .max()
-                   .unwrap();
-   let width = (width as usize).next_power_of_two() as i32;
-   let kmin = -width;
-   let kmax = width;
-   // builds the cube
-   let bots_in_range = (0..bots.len()).collect();
-   Cube { xmin: kmin, xmax: kmax, ymin: kmin, ymax: kmax, zmin: kmin, zmax: kmax, bots_in_range }
+   let distance = cube.cuboid.distance(Metric::Manhattan, &Point::origin());
+   let size = cube.cuboid.longest_side();
+   (cube.bots_in_range.len(), Reverse(distance), Reverse(size))
 }
 
-/// takes a cube and splits it in 8 sub cubes
-/// each cube gets the bots that are in its range
-fn subdivide_cube(cube: &Cube, bots: &[Nanobot]) -> Vec<Cube>
+/// the result of the search and the work needed to find it
+struct Search
 {
-   let xmid = (cube.xmax + cube.xmin) / 2;
-   let ymid = (cube.ymax + cube.ymin) / 2;
-   let zmid = (cube.zmax + cube.zmin) / 2;
-   let mut c1 = Cube { xmin: cube.xmin,
-                       xmax: xmid,
-                       ymin: cube.ymin,
-                       ymax: ymid,
-                       zmin: cube.zmin,
-                       zmax: zmid,
-                       bots_in_range: Vec::new() };
-   c1.bots_in_range =
-      cube.bots_in_range.iter().filter(|&&i| is_cube_in_range(&c1, &bots[i])).cloned().collect();
-   let mut c2 = Cube { xmin: cube.xmin,
-                       xmax: xmid,
-                       ymin: cube.ymin,
-                       ymax: ymid,
-                       zmin: zmid,
-                       zmax: cube.zmax,
-                       bots_in_range: Vec::new() };
-   c2.bots_in_range =
-      cube.bots_in_range.iter().filter(|&&i| is_cube_in_range(&c2, &bots[i])).cloned().collect();
-   let mut c3 = Cube { xmin: cube.xmin,
-                       xmax: xmid,
-                       ymin: ymid,
-                       ymax: cube.ymax,
-                       zmin: cube.zmin,
-                       zmax: zmid,
-                       bots_in_range: Vec::new() };
-   c3.bots_in_range =
-      cube.bots_in_range.iter().filter(|&&i| is_cube_in_range(&c3, &bots[i])).cloned().collect();
-   let mut c4 = Cube { xmin: cube.xmin,
-                       xmax: xmid,
-                       ymin: ymid,
-                       ymax: cube.ymax,
-                       zmin: zmid,
-                       zmax: cube.zmax,
-                       bots_in_range: Vec::new() };
-   c4.bots_in_range =
-      cube.bots_in_range.iter().filter(|&&i| is_cube_in_range(&c4, &bots[i])).cloned().collect();
-   let mut c5 = Cube { xmin: xmid,
-                       xmax: cube.xmax,
-                       ymin: cube.ymin,
-                       ymax: ymid,
-                       zmin: cube.zmin,
-                       zmax: zmid,
-                       bots_in_range: Vec::new() };
-   c5.bots_in_range =
-      cube.bots_in_range.iter().filter(|&&i| is_cube_in_range(&c5, &bots[i])).cloned().collect();
-   let mut c6 = Cube { xmin: xmid,
-                       xmax: cube.xmax,
-                       ymin: cube.ymin,
-                       ymax: ymid,
-                       zmin: zmid,
-                       zmax: cube.zmax,
-                       bots_in_range: Vec::new() };
-   c6.bots_in_range =
-      cube.bots_in_range.iter().filter(|&&i| is_cube_in_range(&c6, &bots[i])).cloned().collect();
-   let mut c7 = Cube { xmin: xmid,
-                       xmax: cube.xmax,
-                       ymin: ymid,
-                       ymax: cube.ymax,
-                       zmin: cube.zmin,
-                       zmax: zmid,
-                       bots_in_range: Vec::new() };
-   c7.bots_in_range =
-      cube.bots_in_range.iter().filter(|&&i| is_cube_in_range(&c7, &bots[i])).cloned().collect();
-   let mut c8 = Cube { xmin: xmid,
-                       xmax: cube.xmax,
-                       ymin: ymid,
-                       ymax: cube.ymax,
-                       zmin: zmid,
-                       zmax: cube.zmax,
-                       bots_in_range: Vec::new() };
-   c8.bots_in_range =
-      cube.bots_in_range.iter().filter(|&&i| is_cube_in_range(&c8, &bots[i])).cloned().collect();
-
-   vec![c1, c2, c3, c4, c5, c6, c7, c8]
+   point: Position,
+   nb_bots: usize,
+   distance: i32,
+   /// number of cubes taken out of the queue
+   nb_cubes_explored: usize,
+   /// number of cubes put in the queue
+   nb_cubes_queued: usize
 }
 
-/// returns the distance to the origin of the cell that :
+/// returns the cell that :
 /// - is in range of the most bots
 /// - is closer to the origin (among cells equaly in range)
-fn task2(bots: &[Nanobot]) -> i32
+fn task2(bots: &[Nanobot]) -> Search
 {
    let mut queue = PriorityQueue::new();
-   queue.push(make_cube(&bots), bots.len());
+   let cube = make_cube(bots, max_coordinate(bots.iter().map(|b| &b.position)));
+   let cube_priority = priority(&cube);
+   queue.push(cube, cube_priority);
 
-   let mut best_weight = 0;
-   let mut best_dist = 0;
-   loop
+   let mut nb_cubes_explored = 0;
+   let mut nb_cubes_queued = 1;
+   while let Some((cube, (nb_bots, Reverse(distance), _))) = queue.pop()
    {
-      match queue.pop()
+      nb_cubes_explored += 1;
+      if cube.cuboid.is_cell()
       {
-         None => return best_dist,                                      // no more cubes
-         Some((_, weight)) if weight < best_weight => return best_dist, // no cube can be better than our current cell
-         Some((ref cube, weight)) if is_cell(cube) =>
-         {
-            // this cube is a cell, is it better than our current cell ?
-            let dist = cube.xmin.abs() + cube.ymin.abs() + cube.zmin.abs();
-            if (weight > best_weight) || ((weight == best_weight) && (dist < best_dist))
-            {
-               best_weight = weight;
-               best_dist = dist;
-            }
-         }
-         Some((cube, _)) =>
-         {
-            // subdivide the cube and keep searching
-            for cube in subdivide_cube(&cube, &bots)
-            {
-               let weight = cube.bots_in_range.len();
-               if weight > 0
-               {
-                  queue.push(cube, weight);
-               }
-            }
-         }
+         // no cube left in the queue can contain a better cell
+         let point = cube.cuboid.min;
+         return Search { point, nb_bots, distance, nb_cubes_explored, nb_cubes_queued };
+      }
+
+      for cube in subdivide_cube(&cube, bots)
+      {
+         let cube_priority = priority(&cube);
+         queue.push(cube, cube_priority);
+         nb_cubes_queued += 1;
       }
    }
+   panic!("The search should end on a cell!")
 }
 
 //-----------------------------------------------------------------------------
@@ -270,6 +177,276 @@ fn main()
 
    // task2
    nanobots.sort_unstable_by_key(|bot| bot.radius);
-   let min_dist = task2(&nanobots);
-   println!("closest bot to origin : {}", min_dist);
+   let search = task2(&nanobots);
+   println!("closest bot to origin : {}", search.distance);
+   println!("point {} in range of {} bots, found after exploring {} cubes ({} queued)",
+            search.point, search.nb_bots, search.nb_cubes_explored, search.nb_cubes_queued);
+
+   // queries
+   let swarm = Swarm::new(&nanobots);
+   println!("{} bots cover the best point", swarm.coverage(&search.point));
+   match swarm.max_coverage_regions(10_000)
+   {
+      Some(coverage) =>
+      {
+         let nb_boxes: usize = coverage.regions.iter().map(|region| region.len()).sum();
+         println!("the cells in range of {} bots form {} region(s) of {} box(es) ({} cubes explored)",
+                  coverage.nb_bots, coverage.regions.len(), nb_boxes, coverage.nb_cubes_explored);
+      }
+      None => println!("the cells in range of the most bots need more than 10000 boxes")
+   }
+   let strongest_bot = nanobots.iter().max_by_key(|bot| bot.radius).unwrap();
+   let origins = [("the origin", Point::origin()), ("the strongest bot", strongest_bot.position)];
+   for &(origin_name, origin) in origins.iter()
+   {
+      for &min_bots in [1, 500, 900, search.nb_bots].iter()
+      {
+         if let Some(closest) = swarm.closest_covered_point(origin, min_bots)
+         {
+            println!("closest point to {} in range of at least {} bots : {} at {} ({} cubes explored)",
+                     origin_name, min_bots, closest.point, closest.distance, closest.nb_cubes_explored);
+         }
+      }
+   }
+   let graph = swarm.overlap_graph();
+   println!("overlap graph : {} edges, max degree {}, {} component(s)",
+            graph.nb_edges(), graph.max_degree(), graph.components().len());
+}
+
+//-----------------------------------------------------------------------------
+// TESTS
+
+#[cfg(test)]
+mod tests
+{
+   use super::*;
+   use std::collections::HashSet;
+
+   /// a xorshift pseudo-random generator
+   struct Random
+   {
+      state: u64
+   }
+
+   impl Random
+   {
+      /// returns a number in min..=max
+      fn between(&mut self, min: i32, max: i32) -> i32
+      {
+         self.state ^= self.state << 13;
+         self.state ^= self.state >> 7;
+         self.state ^= self.state << 17;
+         min + (self.state % (max - min + 1) as u64) as i32
+      }
+   }
+
+   /// tests every cell of the box that contains the bots and the origin
+   /// moving a point toward that box brings it closer to the origin and to all bots so the optimum is inside
+   fn brute_force_task2(bots: &[Nanobot]) -> (usize, i32)
+   {
+      let mut area = Cuboid { min: Point::origin(), max: Point::new([1, 1, 1]) };
+      for bot in bots
+      {
+         for axis in 0..3
+         {
+            area.min[axis] = area.min[axis].min(bot.position[axis]);
+            area.max[axis] = area.max[axis].max(bot.position[axis] + 1);
+         }
+      }
+
+      let (nb_bots, Reverse(distance)) =
+         area.points()
+             .map(|p| {
+                let nb_bots = bots.iter().filter(|bot| is_in_range(bot, p)).count();
+                (nb_bots, Reverse(p.manhattan_distance(&Point::origin())))
+             })
+             .max()
+             .unwrap();
+      (nb_bots, distance)
+   }
+
+   /// checks the search against a brute force and the point it returns against its claims
+   fn check_task2(name: &str, bots: &[Nanobot])
+   {
+      let search = task2(bots);
+      let nb_bots = bots.iter().filter(|bot| is_in_range(bot, search.point)).count();
+      assert_eq!(nb_bots, search.nb_bots, "{} : wrong number of bots around {}", name, search.point);
+      let distance = search.point.manhattan_distance(&Point::origin());
+      assert_eq!(distance, search.distance, "{} : wrong distance", name);
+      let expected = brute_force_task2(bots);
+      assert_eq!((search.nb_bots, search.distance), expected, "{} : the search is not optimal", name);
+   }
+
+   /// inputs in which a lot of cells and cubes share the same number of bots and distance to the origin
+   fn adversarial_inputs() -> Vec<(String, Vec<Nanobot>)>
+   {
+      let bot = |x, y, z, radius| Nanobot { position: Point::new([x, y, z]), radius };
+      let mut inputs = Vec::new();
+
+      // six identical bots, one on each half axis : the best cells are six points at the same distance
+      let axes = vec![bot(20, 0, 0, 3), bot(-20, 0, 0, 3), bot(0, 20, 0, 3),
+                      bot(0, -20, 0, 3), bot(0, 0, 20, 3), bot(0, 0, -20, 3)];
+      inputs.push(("six axes".to_string(), axes));
+
+      // a large bot covering the origin and a small one straddling the axes : only straddling cubes matter
+      let straddling = vec![bot(0, 0, 0, 30), bot(1, -1, 1, 2), bot(-1, 1, -1, 2)];
+      inputs.push(("straddling axes".to_string(), straddling));
+
+      // a bot at a power of two, on the border of the initial cube
+      let border = vec![bot(16, 16, 16, 0), bot(-16, 16, 16, 0)];
+      inputs.push(("border".to_string(), border));
+
+      // a shell of bots whose ranges all touch the same face of an octahedron
+      let shell = (-3..=3).flat_map(|x| (-3..=3).map(move |y| bot(x, y, 12, 4))).collect();
+      inputs.push(("shell".to_string(), shell));
+
+      // random bots with a small range, many cells tie
+      let mut random = Random { state: 0x9E37_79B9_7F4A_7C15 };
+      for seed in 0..30
+      {
+         let bots = (0..10).map(|_| bot(random.between(-15, 15),
+                                        random.between(-15, 15),
+                                        random.between(-15, 15),
+                                        random.between(0, 12)))
+                           .collect();
+         inputs.push((format!("random {}", seed), bots));
+      }
+
+      inputs
+   }
+
+   #[test]
+   fn task2_on_test()
+   {
+      check_task2("test", &input_data("./data/test.txt"));
+   }
+
+   #[test]
+   fn task2_on_adversarial_inputs()
+   {
+      for (name, bots) in &adversarial_inputs()
+      {
+         check_task2(name, bots);
+      }
+   }
+
+   /// checks the queries against brute force over every cell in range of a bot
+   fn check_queries(name: &str, bots: &[Nanobot], random: &mut Random)
+   {
+      let swarm = Swarm::new(bots);
+      let mut area = Cuboid { min: Point::origin(), max: Point::new([1, 1, 1]) };
+      for bot in bots
+      {
+         let bounding_cuboid = range(bot).bounding_cuboid();
+         for axis in 0..3
+         {
+            area.min[axis] = area.min[axis].min(bounding_cuboid.min[axis]);
+            area.max[axis] = area.max[axis].max(bounding_cuboid.max[axis]);
+         }
+      }
+      let coverages: Vec<(Position, usize)> = area.points().map(|p| (p, swarm.coverage(&p))).collect();
+      let max_coverage = coverages.iter().map(|&(_, coverage)| coverage).max().unwrap();
+
+      // regions
+      let coverage = swarm.max_coverage_regions(100_000).expect("Too many boxes.");
+      assert_eq!(coverage.nb_bots, max_coverage, "{} : wrong maximum coverage", name);
+      let best_cells: HashSet<Position> =
+         coverages.iter().filter(|&&(_, coverage)| coverage == max_coverage).map(|&(p, _)| p).collect();
+      let mut region_cells = HashSet::new();
+      for region in &coverage.regions
+      {
+         for cell in region.iter().flat_map(|cuboid| cuboid.points())
+         {
+            assert!(best_cells.contains(&cell), "{} : {} is not in range of the most bots", name, cell);
+            assert!(region_cells.insert(cell), "{} : {} is in two boxes", name, cell);
+         }
+      }
+      assert_eq!(region_cells.len(), best_cells.len(), "{} : some cells are missing from the regions", name);
+      let nb_components = connected_components(&best_cells);
+      assert_eq!(coverage.regions.len(), nb_components, "{} : wrong number of regions", name);
+
+      // closest point to various origins
+      for _ in 0..5
+      {
+         let origin = Point::new([random.between(-20, 20), random.between(-20, 20), random.between(-20, 20)]);
+         let min_bots = random.between(1, max_coverage as i32) as usize;
+         let search = swarm.closest_covered_point(origin, min_bots).expect("There should be a point.");
+         assert!(swarm.coverage(&search.point) >= min_bots, "{} : {} has too few bots", name, search.point);
+         assert_eq!(search.point.manhattan_distance(&origin), search.distance, "{} : wrong distance", name);
+         let distance = coverages.iter()
+                                 .filter(|&&(_, coverage)| coverage >= min_bots)
+                                 .map(|(p, _)| p.manhattan_distance(&origin))
+                                 .min()
+                                 .unwrap();
+         assert_eq!(search.distance, distance, "{} : a closer point exists for {} bots", name, min_bots);
+      }
+      assert!(swarm.closest_covered_point(Point::origin(), max_coverage + 1).is_none());
+
+      // overlaps
+      let graph = swarm.overlap_graph();
+      for (i, neighbours) in graph.neighbours.iter().enumerate()
+      {
+         for j in (0..bots.len()).filter(|&j| j != i)
+         {
+            let (range_i, range_j) = (range(&bots[i]), range(&bots[j]));
+            let overlap =
+               range_i.bounding_cuboid().points().any(|p| range_i.contains(&p) && range_j.contains(&p));
+            assert_eq!(neighbours.contains(&j), overlap, "{} : wrong overlap between {} and {}", name, i, j);
+         }
+      }
+   }
+
+   /// number of groups of cells connected by their faces
+   fn connected_components(cells: &HashSet<Position>) -> usize
+   {
+      let mut is_seen = HashSet::new();
+      let mut nb_components = 0;
+      for &start in cells
+      {
+         if !is_seen.insert(start)
+         {
+            continue;
+         }
+         nb_components += 1;
+         let mut stack = vec![start];
+         while let Some(cell) = stack.pop()
+         {
+            for axis in 0..3
+            {
+               for &delta in [-1, 1].iter()
+               {
+                  let mut neighbour = cell;
+                  neighbour[axis] += delta;
+                  if cells.contains(&neighbour) && is_seen.insert(neighbour)
+                  {
+                     stack.push(neighbour);
+                  }
+               }
+            }
+         }
+      }
+      nb_components
+   }
+
+   #[test]
+   fn queries_on_adversarial_inputs()
+   {
+      let mut random = Random { state: 0x2545_F491_4F6C_DD1D };
+      for (name, bots) in &adversarial_inputs()
+      {
+         check_queries(name, bots, &mut random);
+      }
+   }
+
+   /// the closest point in range of the most bots is the answer to task2
+   #[test]
+   fn closest_point_matches_task2()
+   {
+      let mut nanobots = input_data("./data/input.txt");
+      nanobots.sort_unstable_by_key(|bot| bot.radius);
+      let search = task2(&nanobots);
+      let swarm = Swarm::new(&nanobots);
+      let closest = swarm.closest_covered_point(Point::origin(), search.nb_bots).unwrap();
+      assert_eq!(closest.distance, search.distance, "Both searches should agree on the best point.");
+   }
 }
@@ -0,0 +1,358 @@
+use crate::{make_cube, max_coordinate, range, subdivide_cube, Nanobot, Position, Search};
+use geometry::{Ball, Cuboid, Metric, Point};
+use priority_queue::PriorityQueue;
+use std::cmp::Reverse;
+
+//-----------------------------------------------------------------------------
+// TYPES
+
+/// queries over a set of nanobots
+pub struct Swarm
+{
+   bots: Vec<Nanobot>
+}
+
+/// the cells in range of the most bots, grouped into connected regions
+pub struct Coverage
+{
+   pub nb_bots: usize,
+   /// each region is a set of disjoint boxes, regions do not touch each other
+   pub regions: Vec<Vec<Cuboid<3>>>,
+   /// number of cubes taken out of the queue
+   pub nb_cubes_explored: usize
+}
+
+/// which bots have at least a cell in range of both
+pub struct OverlapGraph
+{
+   pub neighbours: Vec<Vec<usize>>
+}
+
+//-----------------------------------------------------------------------------
+// SWARM
+
+impl Swarm
+{
+   pub fn new(bots: &[Nanobot]) -> Swarm
+   {
+      Swarm { bots: bots.to_vec() }
+   }
+
+   /// number of bots that have the point in range
+   pub fn coverage(&self, point: &Position) -> usize
+   {
+      self.bots.iter().filter(|bot| range(bot).contains(point)).count()
+   }
+
+   /// finds every cell in range of the most bots, as boxes grouped by connected region
+   /// a cube whose cells are all in range of all its bots is kept whole, other cubes are subdivided
+   /// returns None if more than `max_boxes` boxes would be needed
+   pub fn max_coverage_regions(&self, max_boxes: usize) -> Option<Coverage>
+   {
+      // the cells in range of a bot can be further than the bots themselves
+      let reach = self.bots.iter().map(|bot| max_coordinate(std::iter::once(&bot.position)) + bot.radius);
+      let mut queue = PriorityQueue::new();
+      let cube = make_cube(&self.bots, reach.max().unwrap_or(0));
+      let cube_priority = (cube.bots_in_range.len(), Reverse(cube.cuboid.longest_side()));
+      queue.push(cube, cube_priority);
+
+      let mut nb_bots = None;
+      let mut boxes = Vec::new();
+      let mut nb_cubes_explored = 0;
+      while let Some((cube, (nb_bots_in_range, _))) = queue.pop()
+      {
+         // cubes come out with less and less bots, no cube left can reach the maximum
+         if nb_bots.is_some_and(|nb_bots| nb_bots_in_range < nb_bots)
+         {
+            break;
+         }
+         nb_cubes_explored += 1;
+
+         if cube.bots_in_range.iter().all(|&i| range(&self.bots[i]).contains_cuboid(&cube.cuboid))
+         {
+            // the first uniform cube out of the queue is in range of the most bots
+            nb_bots = Some(nb_bots_in_range);
+            boxes.push(cube.cuboid);
+            if boxes.len() > max_boxes
+            {
+               return None;
+            }
+            continue;
+         }
+
+         for cube in subdivide_cube(&cube, &self.bots)
+         {
+            let cube_priority = (cube.bots_in_range.len(), Reverse(cube.cuboid.longest_side()));
+            queue.push(cube, cube_priority);
+         }
+      }
+
+      let regions = connected_regions(boxes);
+      Some(Coverage { nb_bots: nb_bots.unwrap_or(0), regions, nb_cubes_explored })
+   }
+
+   /// returns the cell, in range of at least `min_bots` bots, that is closest to `origin`
+   /// the search runs in diagonal coordinates, where all the faces of the ranges are aligned with the cubes,
+   /// cubes are explored by distance to the origin and shrunk to the part where enough ranges overlap
+   pub fn closest_covered_point(&self, origin: Position, min_bots: usize) -> Option<Search>
+   {
+      let origin_diagonal = diagonal(&origin);
+      let ranges: Vec<Cuboid<4>> = self.bots.iter().map(diagonal_range).collect();
+      let priority = |cube: &DiagonalCube| {
+         let distance = cube.cuboid.distance(Metric::Chebyshev, &origin_diagonal);
+         (Reverse(distance), Reverse(cube.cuboid.longest_side()))
+      };
+
+      // a cube that contains every range and the origin
+      let mut cuboid = Cuboid { min: origin_diagonal, max: origin_diagonal };
+      cuboid.max.0.iter_mut().for_each(|k| *k += 1);
+      for range in &ranges
+      {
+         for axis in 0..4
+         {
+            cuboid.min[axis] = cuboid.min[axis].min(range.min[axis]);
+            cuboid.max[axis] = cuboid.max[axis].max(range.max[axis]);
+         }
+      }
+      let cube = DiagonalCube { cuboid, bots_in_range: (0..self.bots.len()).collect() };
+      let cube_priority = priority(&cube);
+      let mut queue = PriorityQueue::new();
+      queue.push(cube, cube_priority);
+
+      let mut nb_cubes_explored = 0;
+      let mut nb_cubes_queued = 1;
+      while let Some((cube, (Reverse(distance), _))) = queue.pop()
+      {
+         nb_cubes_explored += 1;
+         if cube.cuboid.is_cell()
+         {
+            // no cube left in the queue can contain a closer cell
+            if let Some(point) = position_of_diagonal(&cube.cuboid.min)
+            {
+               let nb_bots = cube.bots_in_range.len();
+               return Some(Search { point, nb_bots, distance, nb_cubes_explored, nb_cubes_queued });
+            }
+            continue;
+         }
+
+         for cuboid in cube.cuboid.subdivide()
+         {
+            // the cuboid is shrunk to the part that can be in range of enough bots
+            let crosses_range = |cuboid: &Cuboid<4>, i: usize| {
+               crosses_positions(&cuboid.intersection(&ranges[i]))
+            };
+            let bots_in_range: Vec<usize> =
+               cube.bots_in_range.iter().cloned().filter(|&i| crosses_range(&cuboid, i)).collect();
+            let cuboid = shrink(&cuboid, &ranges, &bots_in_range, min_bots);
+            let bots_in_range: Vec<usize> =
+               bots_in_range.into_iter().filter(|&i| crosses_range(&cuboid, i)).collect();
+            if bots_in_range.len() >= min_bots && crosses_positions(&cuboid)
+            {
+               let cube = DiagonalCube { cuboid, bots_in_range };
+               let cube_priority = priority(&cube);
+               queue.push(cube, cube_priority);
+               nb_cubes_queued += 1;
+            }
+         }
+      }
+      None
+   }
+
+   /// two bots overlap if their ranges share a cell
+   /// on a grid, it is the case as soon as their distance is below the sum of their radius
+   pub fn overlap_graph(&self) -> OverlapGraph
+   {
+      let overlap = |i: usize, j: usize| {
+         let (b1, b2) = (&self.bots[i], &self.bots[j]);
+         i != j && b1.position.manhattan_distance(&b2.position) <= b1.radius + b2.radius
+      };
+      let nb_bots = self.bots.len();
+      let neighbours = (0..nb_bots).map(|i| (0..nb_bots).filter(|&j| overlap(i, j)).collect()).collect();
+      OverlapGraph { neighbours }
+   }
+}
+
+//-----------------------------------------------------------------------------
+// DIAGONAL
+
+/// a cube in diagonal coordinates and the bots whose range intersects it
+#[derive(Hash, Eq, PartialEq)]
+struct DiagonalCube
+{
+   cuboid: Cuboid<4>,
+   bots_in_range: Vec<usize>
+}
+
+/// coordinates along the diagonals (x+y+z, x+y-z, x-y+z, -x+y+z)
+/// the manhattan distance between two positions is the chebyshev distance between their diagonal coordinates
+fn diagonal(p: &Position) -> Point<4>
+{
+   Point::new([p[0] + p[1] + p[2], p[0] + p[1] - p[2], p[0] - p[1] + p[2], -p[0] + p[1] + p[2]])
+}
+
+/// the position with the given diagonal coordinates, if there is one
+/// a position needs u0 = u1 + u2 + u3 and u1, u2, u3 of the same parity
+fn position_of_diagonal(u: &Point<4>) -> Option<Position>
+{
+   let is_position = u[0] == u[1] + u[2] + u[3] && (u[1] - u[2]) % 2 == 0 && (u[1] - u[3]) % 2 == 0;
+   if is_position
+   {
+      Some(Point::new([(u[1] + u[2]) / 2, (u[1] + u[3]) / 2, (u[2] + u[3]) / 2]))
+   }
+   else
+   {
+      None
+   }
+}
+
+/// the range of a bot in diagonal coordinates is a box
+fn diagonal_range(bot: &Nanobot) -> Cuboid<4>
+{
+   Ball::chebyshev(diagonal(&bot.position), bot.radius).bounding_cuboid()
+}
+
+/// returns false if the cuboid contains no diagonal coordinates of a position
+/// (it might return true for a cuboid that only contains coordinates of the wrong parity)
+fn crosses_positions(cuboid: &Cuboid<4>) -> bool
+{
+   // an empty cuboid can have extreme bounds (see `shrink`), whose sums would overflow
+   if cuboid.is_empty()
+   {
+      return false;
+   }
+   let sum_min = cuboid.min[1] + cuboid.min[2] + cuboid.min[3];
+   let sum_max = (cuboid.max[1] - 1) + (cuboid.max[2] - 1) + (cuboid.max[3] - 1);
+   cuboid.min[0] <= sum_max && sum_min < cuboid.max[0]
+}
+
+/// shrinks the cuboid to the coordinates that are in at least `min_bots` of the ranges along each axis
+/// no point outside of the result can be in range of `min_bots` bots
+fn shrink(cuboid: &Cuboid<4>, ranges: &[Cuboid<4>], bots: &[usize], min_bots: usize) -> Cuboid<4>
+{
+   let mut shrunk = *cuboid;
+   let mut events = Vec::with_capacity(2 * bots.len());
+   for axis in 0..4
+   {
+      // a range ends before the next one starts on the same coordinate as the max is excluded
+      events.clear();
+      for &i in bots
+      {
+         events.push((ranges[i].min[axis].max(cuboid.min[axis]), 1));
+         events.push((ranges[i].max[axis].min(cuboid.max[axis]), -1));
+      }
+      events.sort_unstable();
+
+      // the depth is constant between two successive coordinates
+      let (mut min, mut max) = (i32::MAX, i32::MIN);
+      let mut depth = 0;
+      for (event, next_event) in events.iter().zip(events.iter().skip(1))
+      {
+         depth += event.1;
+         if depth >= min_bots as i32 && event.0 < next_event.0
+         {
+            min = min.min(event.0);
+            max = max.max(next_event.0);
+         }
+      }
+      shrunk.min[axis] = min;
+      shrunk.max[axis] = max;
+   }
+   shrunk
+}
+
+/// returns true if the boxes share a face
+fn are_adjacent(c1: &Cuboid<3>, c2: &Cuboid<3>) -> bool
+{
+   let mut nb_touching_axes = 0;
+   for axis in 0..3
+   {
+      if c1.max[axis] == c2.min[axis] || c2.max[axis] == c1.min[axis]
+      {
+         nb_touching_axes += 1;
+      }
+      else if c1.max[axis] < c2.min[axis] || c2.max[axis] < c1.min[axis]
+      {
+         return false;
+      }
+   }
+   nb_touching_axes == 1
+}
+
+/// groups boxes into sets of boxes connected by their faces
+fn connected_regions(boxes: Vec<Cuboid<3>>) -> Vec<Vec<Cuboid<3>>>
+{
+   let mut region_of_box: Vec<Option<usize>> = vec![None; boxes.len()];
+   let mut regions = Vec::new();
+   for start in 0..boxes.len()
+   {
+      if region_of_box[start].is_some()
+      {
+         continue;
+      }
+      let region = regions.len();
+      region_of_box[start] = Some(region);
+      let mut members = vec![start];
+      let mut stack = vec![start];
+      while let Some(b) = stack.pop()
+      {
+         for other in 0..boxes.len()
+         {
+            if region_of_box[other].is_none() && are_adjacent(&boxes[b], &boxes[other])
+            {
+               region_of_box[other] = Some(region);
+               members.push(other);
+               stack.push(other);
+            }
+         }
+      }
+      regions.push(members.into_iter().map(|b| boxes[b]).collect());
+   }
+   regions
+}
+
+//-----------------------------------------------------------------------------
+// GRAPH
+
+impl OverlapGraph
+{
+   pub fn nb_edges(&self) -> usize
+   {
+      self.neighbours.iter().map(|n| n.len()).sum::<usize>() / 2
+   }
+
+   pub fn max_degree(&self) -> usize
+   {
+      self.neighbours.iter().map(|n| n.len()).max().unwrap_or(0)
+   }
+
+   /// groups of bots linked by overlaps
+   pub fn components(&self) -> Vec<Vec<usize>>
+   {
+      let mut is_seen = vec![false; self.neighbours.len()];
+      let mut components = Vec::new();
+      for start in 0..self.neighbours.len()
+      {
+         if is_seen[start]
+         {
+            continue;
+         }
+         is_seen[start] = true;
+         let mut component = vec![start];
+         let mut stack = vec![start];
+         while let Some(bot) = stack.pop()
+         {
+            for &other in &self.neighbours[bot]
+            {
+               if !is_seen[other]
+               {
+                  is_seen[other] = true;
+                  component.push(other);
+                  stack.push(other);
+               }
+            }
+         }
+         components.push(component);
+      }
+      components
+   }
+}
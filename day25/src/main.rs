@@ -6,7 +6,7 @@ use std::io::{BufRead, BufReader};
 //-----------------------------------------------------------------------------
 // TYPE
 
-type Point = (i32, i32, i32, i32);
+type Point = geometry::Point<4>;
 
 type Constelation = Vec<Point>;
 
@@ -17,7 +17,7 @@ type Constelation = Vec<Point>;
 fn parse_point(line: &str) -> Point
 {
    let (x, y, z, t) = scan_fmt!(line, "{},{},{},{}", i32, i32, i32, i32);
-   (x.unwrap(), y.unwrap(), z.unwrap(), t.unwrap())
+   Point::new([x.unwrap(), y.unwrap(), z.unwrap(), t.unwrap()])
 }
 
 /// turns a file into a vector of points
@@ -33,16 +33,10 @@ fn input_data(path: &str) -> Vec<Point>
 //-----------------------------------------------------------------------------
 // TASK1
 
-/// computes the manhatan distance between two points
-fn manhatant_distance(p1: &Point, p2: &Point) -> i32
-{
-   (p1.0 - p2.0).abs() + (p1.1 - p2.1).abs() + (p1.2 - p2.2).abs() + (p1.3 - p2.3).abs()
-}
-
 /// is the point in the constelation
 fn is_in_constelation(p: &Point, c: &[Point]) -> bool
 {
-   c.iter().any(|p2| manhatant_distance(p, p2) < 4)
+   c.iter().any(|p2| p.manhattan_distance(p2) < 4)
 }
 
 /// put each point into a constelation
@@ -7,17 +7,13 @@ use std::io::{BufRead, BufReader};
 //-----------------------------------------------------------------------------
 // INPUT
 
-struct Point
-{
-   x: i32,
-   y: i32
-}
+type Point = geometry::Point<2>;
 
 /// parses a line to produce a point
 fn parse_point(line: &str) -> Point
 {
    let (x, y) = scan_fmt!(line, "{}, {}", i32, i32);
-   Point { x: x.unwrap(), y: y.unwrap() }
+   Point::new([x.unwrap(), y.unwrap()])
 }
 
 /// reads a file line by line and converts each line to a point
@@ -33,12 +29,6 @@ fn read_inputs(path: &str) -> Vec<Point>
 //-----------------------------------------------------------------------------
 // TASK1
 
-/// returns the manhattan distance between two points
-fn manhattan_distance(p1: &Point, p2: &Point) -> i32
-{
-   (p1.x - p2.x).abs() + (p1.y - p2.y).abs()
-}
-
 /// returns the index of the closest point in the absence of a tie
 fn closest_point(coordinate: &Point, points: &[Point]) -> Option<usize>
 {
@@ -48,7 +38,7 @@ fn closest_point(coordinate: &Point, points: &[Point]) -> Option<usize>
 
    for (i, p) in points.iter().enumerate()
    {
-      match manhattan_distance(coordinate, p)
+      match coordinate.manhattan_distance(p)
       {
          d if d == best_dist => is_tie = true,
          d if d < best_dist =>
@@ -74,8 +64,8 @@ fn closest_point(coordinate: &Point, points: &[Point]) -> Option<usize>
 /// returns the area of the largest, non infinite, voronoy polygon
 fn task1(points: &[Point]) -> usize
 {
-   let (xmin, xmax) = points.iter().map(|p| p.x).minmax().into_option().unwrap();
-   let (ymin, ymax) = points.iter().map(|p| p.y).minmax().into_option().unwrap();
+   let (xmin, xmax) = points.iter().map(|p| p[0]).minmax().into_option().unwrap();
+   let (ymin, ymax) = points.iter().map(|p| p[1]).minmax().into_option().unwrap();
 
    // find area of all polygones whithin a rectangle that contains all points
    let mut areas = vec![0; points.len()];
@@ -84,7 +74,7 @@ fn task1(points: &[Point]) -> usize
    {
       for y in ymin..=ymax
       {
-         match closest_point(&Point { x, y }, points)
+         match closest_point(&Point::new([x, y]), points)
          {
             Some(i) if x == xmin || x == xmax || y == ymin || y == ymax => finite[i] = false,
             Some(i) => areas[i] += 1,
@@ -103,17 +93,17 @@ fn task1(points: &[Point]) -> usize
 /// returns the sum of distance to all points
 fn total_distance(coordinate: &Point, points: &[Point]) -> i32
 {
-   points.iter().map(|p| manhattan_distance(coordinate, p)).sum()
+   points.iter().map(|p| coordinate.manhattan_distance(p)).sum()
 }
 
 /// returns the area of the section were is point is within max_dist of total distance to all points
 fn task2(points: &[Point], max_dist: i32) -> usize
 {
-   let (xmin, xmax) = points.iter().map(|p| p.x).minmax().into_option().unwrap();
-   let (ymin, ymax) = points.iter().map(|p| p.y).minmax().into_option().unwrap();
+   let (xmin, xmax) = points.iter().map(|p| p[0]).minmax().into_option().unwrap();
+   let (ymin, ymax) = points.iter().map(|p| p[1]).minmax().into_option().unwrap();
 
    (xmin..=xmax).cartesian_product(ymin..=ymax)
-                .map(|(x, y)| Point { x, y })
+                .map(|(x, y)| Point::new([x, y]))
                 .filter(|p| total_distance(p, points) < max_dist)
                 .count()
 }
@@ -0,0 +1,420 @@
+use std::fmt;
+use std::ops::{Index, IndexMut};
+
+//-----------------------------------------------------------------------------
+// TYPES
+
+/// a point with N integer coordinates
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Point<const N: usize>(pub [i32; N]);
+
+/// the ways to measure a distance between points
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Metric
+{
+   /// sum of the distances along each axis
+   Manhattan,
+   /// max of the distances along each axis
+   Chebyshev
+}
+
+/// an axis aligned box, the min is included and the max excluded in each dimension
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Cuboid<const N: usize>
+{
+   pub min: Point<N>,
+   pub max: Point<N>
+}
+
+/// the points within `radius` of a center (bounds included)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Ball<const N: usize>
+{
+   pub center: Point<N>,
+   pub radius: i32,
+   pub metric: Metric
+}
+
+//-----------------------------------------------------------------------------
+// POINT
+
+impl<const N: usize> Point<N>
+{
+   pub fn new(coordinates: [i32; N]) -> Point<N>
+   {
+      Point(coordinates)
+   }
+
+   pub fn origin() -> Point<N>
+   {
+      Point([0; N])
+   }
+
+   pub fn manhattan_distance(&self, other: &Point<N>) -> i32
+   {
+      Metric::Manhattan.distance(self, other)
+   }
+
+   pub fn chebyshev_distance(&self, other: &Point<N>) -> i32
+   {
+      Metric::Chebyshev.distance(self, other)
+   }
+}
+
+impl<const N: usize> Index<usize> for Point<N>
+{
+   type Output = i32;
+
+   fn index(&self, axis: usize) -> &i32
+   {
+      &self.0[axis]
+   }
+}
+
+impl<const N: usize> IndexMut<usize> for Point<N>
+{
+   fn index_mut(&mut self, axis: usize) -> &mut i32
+   {
+      &mut self.0[axis]
+   }
+}
+
+/// displays the point as `<x,y,z>`
+impl<const N: usize> fmt::Display for Point<N>
+{
+   fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+   {
+      write!(f, "<")?;
+      for (axis, k) in self.0.iter().enumerate()
+      {
+         if axis > 0
+         {
+            write!(f, ",")?;
+         }
+         write!(f, "{}", k)?;
+      }
+      write!(f, ">")
+   }
+}
+
+//-----------------------------------------------------------------------------
+// METRIC
+
+impl Metric
+{
+   /// combines the distances along each axis into a distance
+   fn combine<I: Iterator<Item = i32>>(self, distances: I) -> i32
+   {
+      match self
+      {
+         Metric::Manhattan => distances.sum(),
+         Metric::Chebyshev => distances.max().unwrap_or(0)
+      }
+   }
+
+   pub fn distance<const N: usize>(self, p1: &Point<N>, p2: &Point<N>) -> i32
+   {
+      self.combine(p1.0.iter().zip(p2.0.iter()).map(|(k1, k2)| (k1 - k2).abs()))
+   }
+}
+
+//-----------------------------------------------------------------------------
+// CUBOID
+
+impl<const N: usize> Cuboid<N>
+{
+   /// the cuboid [-half_width;half_width[ in every dimension
+   pub fn centered(half_width: i32) -> Cuboid<N>
+   {
+      Cuboid { min: Point([-half_width; N]), max: Point([half_width; N]) }
+   }
+
+   pub fn is_empty(&self) -> bool
+   {
+      (0..N).any(|axis| self.min[axis] >= self.max[axis])
+   }
+
+   /// returns true if the cuboid contains a single point
+   pub fn is_cell(&self) -> bool
+   {
+      (0..N).all(|axis| self.max[axis] - self.min[axis] == 1)
+   }
+
+   /// length of the longest side of the cuboid
+   pub fn longest_side(&self) -> i32
+   {
+      (0..N).map(|axis| self.max[axis] - self.min[axis]).max().unwrap_or(0)
+   }
+
+   pub fn contains(&self, point: &Point<N>) -> bool
+   {
+      (0..N).all(|axis| self.min[axis] <= point[axis] && point[axis] < self.max[axis])
+   }
+
+   /// the points in both cuboids (which might be empty)
+   pub fn intersection(&self, other: &Cuboid<N>) -> Cuboid<N>
+   {
+      let mut cuboid = *self;
+      for axis in 0..N
+      {
+         cuboid.min[axis] = self.min[axis].max(other.min[axis]);
+         cuboid.max[axis] = self.max[axis].min(other.max[axis]);
+      }
+      cuboid
+   }
+
+   /// all the points of the cuboid, the last dimension varying fastest
+   pub fn points(&self) -> impl Iterator<Item = Point<N>> + '_
+   {
+      let sides: Vec<i64> = (0..N).map(|axis| (self.max[axis] - self.min[axis]).max(0) as i64).collect();
+      let volume: i64 = sides.iter().product();
+      (0..volume).map(move |mut index| {
+                    let mut point = self.min;
+                    for axis in (0..N).rev()
+                    {
+                       point[axis] += (index % sides[axis]) as i32;
+                       index /= sides[axis];
+                    }
+                    point
+                 })
+   }
+
+   /// the point of the cuboid that is closest to the given point (for all metrics)
+   pub fn closest_point(&self, point: &Point<N>) -> Point<N>
+   {
+      let mut closest = *point;
+      for axis in 0..N
+      {
+         closest[axis] = point[axis].max(self.min[axis]).min(self.max[axis] - 1);
+      }
+      closest
+   }
+
+   /// the point of the cuboid that is farthest from the given point (for all metrics)
+   pub fn farthest_point(&self, point: &Point<N>) -> Point<N>
+   {
+      let mut farthest = *point;
+      for axis in 0..N
+      {
+         let (low, high) = (self.min[axis], self.max[axis] - 1);
+         farthest[axis] = if (point[axis] - low).abs() >= (high - point[axis]).abs() { low } else { high };
+      }
+      farthest
+   }
+
+   /// shortest distance between a point and the cuboid, 0 if the point is inside
+   pub fn distance(&self, metric: Metric, point: &Point<N>) -> i32
+   {
+      metric.distance(&self.closest_point(point), point)
+   }
+
+   /// splits the cuboid in two along each dimension, giving up to 2^N cuboids
+   /// the first dimension varies slowest, dimensions of length 1 are not split
+   pub fn subdivide(&self) -> Vec<Cuboid<N>>
+   {
+      let mut mid = self.min;
+      for axis in 0..N
+      {
+         mid[axis] = (self.min[axis] + self.max[axis]) / 2;
+      }
+
+      (0..1usize << N).map(|index| {
+                         let mut cuboid = *self;
+                         for axis in 0..N
+                         {
+                            if index & (1 << (N - 1 - axis)) == 0
+                            {
+                               cuboid.max[axis] = mid[axis];
+                            }
+                            else
+                            {
+                               cuboid.min[axis] = mid[axis];
+                            }
+                         }
+                         cuboid
+                      })
+                      .filter(|cuboid| !cuboid.is_empty())
+                      .collect()
+   }
+}
+
+//-----------------------------------------------------------------------------
+// BALL
+
+impl<const N: usize> Ball<N>
+{
+   pub fn manhattan(center: Point<N>, radius: i32) -> Ball<N>
+   {
+      Ball { center, radius, metric: Metric::Manhattan }
+   }
+
+   pub fn chebyshev(center: Point<N>, radius: i32) -> Ball<N>
+   {
+      Ball { center, radius, metric: Metric::Chebyshev }
+   }
+
+   pub fn contains(&self, point: &Point<N>) -> bool
+   {
+      self.metric.distance(&self.center, point) <= self.radius
+   }
+
+   /// returns true if at least a point of the cuboid is in the ball
+   pub fn intersects(&self, cuboid: &Cuboid<N>) -> bool
+   {
+      !cuboid.is_empty() && cuboid.distance(self.metric, &self.center) <= self.radius
+   }
+
+   /// returns true if every point of the cuboid is in the ball
+   pub fn contains_cuboid(&self, cuboid: &Cuboid<N>) -> bool
+   {
+      cuboid.is_empty() || self.contains(&cuboid.farthest_point(&self.center))
+   }
+
+   /// the smallest cuboid that contains the ball
+   pub fn bounding_cuboid(&self) -> Cuboid<N>
+   {
+      let mut cuboid = Cuboid { min: self.center, max: self.center };
+      for axis in 0..N
+      {
+         cuboid.min[axis] -= self.radius;
+         cuboid.max[axis] += self.radius + 1;
+      }
+      cuboid
+   }
+}
+
+//-----------------------------------------------------------------------------
+// TESTS
+
+#[cfg(test)]
+mod tests
+{
+   use super::*;
+
+   /// a xorshift pseudo-random generator
+   struct Random
+   {
+      state: u64
+   }
+
+   impl Random
+   {
+      /// returns a number in min..=max
+      fn between(&mut self, min: i32, max: i32) -> i32
+      {
+         self.state ^= self.state << 13;
+         self.state ^= self.state >> 7;
+         self.state ^= self.state << 17;
+         min + (self.state % (max - min + 1) as u64) as i32
+      }
+   }
+
+   /// returns a small random cuboid
+   fn random_cuboid<const N: usize>(random: &mut Random) -> Cuboid<N>
+   {
+      let mut cuboid = Cuboid { min: Point::origin(), max: Point::origin() };
+      for axis in 0..N
+      {
+         cuboid.min[axis] = random.between(-6, 6);
+         cuboid.max[axis] = cuboid.min[axis] + random.between(1, 5);
+      }
+      cuboid
+   }
+
+   /// checks the toolkit against brute force on small random cuboids and balls
+   fn check_geometry<const N: usize>(seed: u64, nb_cases: usize)
+   {
+      let mut random = Random { state: seed };
+      for _ in 0..nb_cases
+      {
+         let cuboid: Cuboid<N> = random_cuboid(&mut random);
+         let mut center = Point::origin();
+         for axis in 0..N
+         {
+            center[axis] = random.between(-10, 10);
+         }
+         let radius = random.between(0, 8);
+         let ball = match random.between(0, 1)
+         {
+            0 => Ball::manhattan(center, radius),
+            _ => Ball::chebyshev(center, radius)
+         };
+
+         // distances
+         let distance = cuboid.points().map(|p| ball.metric.distance(&p, &center)).min().unwrap();
+         assert_eq!(cuboid.distance(ball.metric, &center), distance, "wrong distance to {:?}", cuboid);
+         let closest = cuboid.closest_point(&center);
+         assert!(cuboid.contains(&closest) && ball.metric.distance(&closest, &center) == distance);
+         let farthest = cuboid.points().map(|p| ball.metric.distance(&p, &center)).max().unwrap();
+         let farthest_point = cuboid.farthest_point(&center);
+         assert!(cuboid.contains(&farthest_point), "{} is not in {:?}", farthest_point, cuboid);
+         assert_eq!(ball.metric.distance(&farthest_point, &center), farthest, "wrong farthest point");
+
+         // balls
+         let intersects = cuboid.points().any(|p| ball.contains(&p));
+         assert_eq!(ball.intersects(&cuboid), intersects, "wrong intersection with {:?}", ball);
+         let is_contained = cuboid.points().all(|p| ball.contains(&p));
+         assert_eq!(ball.contains_cuboid(&cuboid), is_contained, "wrong inclusion in {:?}", ball);
+         let bounding_cuboid = ball.bounding_cuboid();
+         assert!(cuboid.points().filter(|p| ball.contains(p)).all(|p| bounding_cuboid.contains(&p)));
+         assert_eq!(bounding_cuboid.longest_side(), 2 * radius + 1);
+
+         // intersections
+         let other = random_cuboid(&mut random);
+         let intersection = cuboid.intersection(&other);
+         let nb_common_points = cuboid.points().filter(|p| other.contains(p)).count();
+         if intersection.is_empty()
+         {
+            assert_eq!(nb_common_points, 0, "{:?} and {:?} should not intersect", cuboid, other);
+         }
+         else
+         {
+            assert!(intersection.points().all(|p| cuboid.contains(&p) && other.contains(&p)));
+            assert_eq!(intersection.points().count(), nb_common_points, "wrong intersection of {:?}", cuboid);
+         }
+
+         // subdivision
+         let children = cuboid.subdivide();
+         assert!(children.len() <= 1 << N);
+         for p in cuboid.points()
+         {
+            let nb_children = children.iter().filter(|child| child.contains(&p)).count();
+            assert_eq!(nb_children, 1, "{} should be in exactly one child of {:?}", p, cuboid);
+         }
+         let nb_points: usize = children.iter().map(|child| child.points().count()).sum();
+         assert_eq!(nb_points, cuboid.points().count(), "the children of {:?} overflow", cuboid);
+         assert_eq!(cuboid.is_cell(), cuboid.points().count() == 1);
+      }
+   }
+
+   #[test]
+   fn geometry_2d()
+   {
+      check_geometry::<2>(0x2545_F491_4F6C_DD1D, 500);
+   }
+
+   #[test]
+   fn geometry_3d()
+   {
+      check_geometry::<3>(0x9E37_79B9_7F4A_7C15, 500);
+   }
+
+   #[test]
+   fn geometry_4d()
+   {
+      check_geometry::<4>(0xD1B5_4A32_D192_ED03, 500);
+   }
+
+   #[test]
+   fn points()
+   {
+      let (p1, p2) = (Point::new([1, -2, 3]), Point::new([-1, 2, 3]));
+      assert_eq!(p1.manhattan_distance(&p2), 6);
+      assert_eq!(p1.chebyshev_distance(&p2), 4);
+      assert_eq!(p1.to_string(), "<1,-2,3>");
+      let cuboid = Cuboid::<3>::centered(2);
+      assert!(cuboid.contains(&Point::new([-2, -2, 1])) && !cuboid.contains(&Point::new([2, 0, 0])));
+      assert_eq!(cuboid.points().next(), Some(Point::new([-2, -2, -2])));
+      assert_eq!(cuboid.points().nth(1), Some(Point::new([-2, -2, -1])));
+   }
+}